@@ -0,0 +1,65 @@
+//! Compares reading a ROM bank through the cartridge, which keeps the ROM in memory,
+//! against seeking the ROM file for every byte, as the cartridge used to.
+//!
+//! Run with `cargo bench --bench cartridge`.
+
+use std::{
+    fs::{self, File},
+    hint::black_box,
+    io::{Read, Seek, SeekFrom},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use playful_youngster::hardware::Cartridge;
+
+/// Number of 16 KiB banks of the synthetic ROM: 512 KiB, as for many MBC1 games.
+const BANKS: usize = 32;
+const BANK_SIZE: usize = 0x4000;
+
+/// Builds an MBC1 ROM whose bytes are all different from their neighbours.
+fn rom() -> Vec<u8> {
+    let mut rom: Vec<u8> = (0..BANKS * BANK_SIZE).map(|i| (i % 251) as u8).collect();
+    rom[0x147] = 0x01; // MBC1
+    rom[0x148] = 0x04; // 32 banks
+    rom[0x149] = 0x00; // No RAM
+    rom
+}
+
+/// Reads a byte the way the cartridge did before ROMs were loaded into memory.
+fn seek_and_read(source: &mut (impl Read + Seek), addr: u64) -> u8 {
+    source.seek(SeekFrom::Start(addr)).unwrap();
+    let mut buf = [0; 1];
+    source.read_exact(&mut buf).unwrap();
+    buf[0]
+}
+
+fn read_bank(c: &mut Criterion) {
+    let rom = rom();
+    let path = std::env::temp_dir().join("playful-youngster-bench.gb");
+    fs::write(&path, &rom).unwrap();
+
+    let mut group = c.benchmark_group("read_bank");
+    group.throughput(Throughput::Bytes(BANK_SIZE as u64));
+
+    let mut cart = Cartridge::new_from_header(File::open(&path).unwrap()).unwrap();
+    cart.write(0x2000, 5);
+    group.bench_function(BenchmarkId::new("in_memory", "mbc1"), |b| {
+        b.iter(|| (0x4000..0x8000).fold(0u8, |acc, addr| acc ^ black_box(cart.read(addr))))
+    });
+
+    let base = 5 * BANK_SIZE as u64;
+    let mut file = File::open(&path).unwrap();
+    group.bench_function(BenchmarkId::new("seek_per_byte", "file"), |b| {
+        b.iter(|| {
+            (0..BANK_SIZE as u64).fold(0u8, |acc, i| {
+                acc ^ black_box(seek_and_read(&mut file, base + i))
+            })
+        })
+    });
+
+    group.finish();
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, read_bank);
+criterion_main!(benches);
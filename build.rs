@@ -1,9 +1,14 @@
+use std::env;
 use std::process::Command;
 use std::process::ExitStatus;
 
 const BOOTROM_PATH: &str = "bootrom";
 
 fn main() -> Result<(), ExitStatus> {
+    // The boot ROM is only needed when it is embedded in the emulator.
+    if env::var_os("CARGO_FEATURE_BOOTROM").is_none() {
+        return Ok(());
+    }
     let bootrom = Command::new("make")
         .current_dir(BOOTROM_PATH)
         .spawn()
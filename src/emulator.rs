@@ -1,43 +1,181 @@
-use crate::hardware::{self, keypad::Button, Cartridge, Cpu, Hardware};
-use std::{sync::mpsc, thread, time};
+pub mod debugger;
+pub mod gdb;
+
+use crate::hardware::{
+    self, keypad::Button, Access, BootRom, Cartridge, Cpu, Hardware, Model, Recorder, Registers,
+};
+use std::{io, sync::mpsc, thread, time};
 
 /// Target framerate (aka FPS) for the emulator.
 const FRAMERATE: u32 = 60;
+/// Frames between saves of battery-backed cartridges, in case the emulator does not exit cleanly.
+const SAVE_INTERVAL: u32 = 10 * FRAMERATE;
 pub use hardware::apu::SAMPLE_RATE;
 
 pub struct Emulator {
     cpu: Cpu,
     hw: Hardware,
+    /// Frames processed since the cartridge was last saved.
+    frames_since_save: u32,
 }
 
 impl Emulator {
     pub fn new(audio_buffer: mpsc::SyncSender<(u8, u8)>) -> Self {
-        Self {
+        let mut emu = Self {
             cpu: Cpu::new(),
             hw: Hardware::new(audio_buffer),
+            frames_since_save: 0,
+        };
+        // Without a boot ROM to run, start right where it would have ended.
+        if !emu.hw.booting() {
+            emu.skip_boot();
         }
+        emu
+    }
+
+    /// Replaces the boot ROM, and emulates the model it belongs to. The CPU is reset,
+    /// so the boot ROM runs from its start even if boot was skipped already.
+    /// It is meant to be called before running.
+    pub fn insert_boot_rom(&mut self, boot_rom: BootRom) {
+        self.hw.insert_boot_rom(boot_rom);
+        self.cpu.reset();
     }
 
+    /// Sets the model to emulate when skipping boot. Inserting a boot ROM sets it too.
+    /// If boot was skipped already, it is skipped again, to leave the state of this model.
+    /// It is meant to be called before running.
+    pub fn set_model(&mut self, model: Model) {
+        self.hw.set_model(model);
+        if !self.hw.booting() {
+            self.skip_boot();
+        }
+    }
+
+    /// Skips the boot ROM, leaving the system as the boot ROM of the emulated model
+    /// leaves it when it jumps to the cartridge. It is meant to be called before running.
+    pub fn skip_boot(&mut self) {
+        *self.cpu.regs_mut() = Registers::after_boot(self.hw.model());
+        self.hw.skip_boot();
+    }
+
+    /// Inserts a cartridge. To keep the progress of a battery-backed one,
+    /// give it a storage with [`Cartridge::set_storage`] first.
     pub fn insert_cartridge(&mut self, cart: Cartridge) {
         self.hw.insert_cartridge(cart);
     }
 
+    /// Saves the cartridge, if it has a battery. It is meant to be called before exiting,
+    /// as [`Self::process_frame`] only saves every few seconds.
+    pub fn save(&mut self) -> io::Result<()> {
+        self.frames_since_save = 0;
+        self.hw.save()
+    }
+
+    /// Returns whether the rumble motor of the cartridge was turned on (`true`) or off (`false`)
+    /// since the last call, if it changed at all. Frontends are meant to poll it every frame.
+    pub fn take_rumble(&mut self) -> Option<bool> {
+        self.hw.take_rumble()
+    }
+
+    /// Tilts the console, for cartridges with an accelerometer: `x` and `y` are in g along
+    /// the axes of the screen, from -1.0 to 1.0 when tilting left or up and right or down by 90°.
+    /// Frontends can drive it from keys, a mouse or a real sensor; it stays until changed.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.hw.set_tilt(x, y);
+    }
+
+    /// Logs every executed instruction to `sink`, or stops logging if `None`.
+    /// See [`Cpu::set_trace`] for the format.
+    pub fn set_trace(&mut self, sink: Option<Box<dyn io::Write>>) {
+        self.cpu.set_trace(sink);
+    }
+
     pub fn set_pressed(&mut self, button: Button, pressed: bool) {
         self.hw.keypad.set_pressed(button, pressed);
     }
 
-    pub fn process_frame(&mut self) {
+    /// Runs the emulation for a frame, then saves the cartridge if the game is done writing it,
+    /// or if it was not saved in a while.
+    pub fn process_frame(&mut self) -> io::Result<()> {
         const TICKS_IN_FRAMERATE: u32 = hardware::MASTER_CLOCK / FRAMERATE;
         const FRAMETIME: f32 = 1.0 / (FRAMERATE as f32);
 
         let mut total_ticks = 0;
         let duration = time::Instant::now();
         while total_ticks < TICKS_IN_FRAMERATE {
-            let ticks = self.cpu.tick(&mut self.hw);
-            self.hw.timer.tick(ticks);
-            self.hw.apu.tick(ticks);
-            total_ticks += ticks as u32;
+            total_ticks += self.cpu.tick(&mut self.hw) as u32;
         }
         thread::sleep(time::Duration::from_secs_f32(FRAMETIME).saturating_sub(duration.elapsed()));
+
+        self.frames_since_save += 1;
+        if self.hw.save_requested() || self.frames_since_save >= SAVE_INTERVAL {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Executes one instruction, and returns every memory access it made.
+    fn step_recorded(&mut self) -> Vec<Access> {
+        let mut bus = Recorder::new(&mut self.hw);
+        self.cpu.tick(&mut bus);
+        bus.take_accesses()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_rom_runs_after_skipped_boot() {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let mut emu = Emulator::new(sender);
+        // Without the `bootrom` feature, boot was skipped already.
+        emu.skip_boot();
+        let mut data = vec![0x00; 256];
+        data[0] = 0x3C; // INC A
+        emu.insert_boot_rom(BootRom::with_model(data, Model::Dmg).unwrap());
+
+        assert_eq!(emu.step_recorded()[0], Access::Read(0x0000, 0x3C));
+        assert_eq!(emu.cpu.regs().a, 0x01);
+        assert_eq!(emu.cpu.regs().prog_counter, 0x0001);
+    }
+
+    #[test]
+    fn model_set_after_skipped_boot() {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let mut emu = Emulator::new(sender);
+        emu.skip_boot();
+        emu.set_model(Model::Mgb);
+        assert_eq!(emu.cpu.regs().a, 0xFF);
+    }
+
+    #[test]
+    fn frame_after_skipped_boot() {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let mut emu = Emulator::new(sender);
+        emu.skip_boot();
+        emu.process_frame().unwrap();
+    }
+
+    #[test]
+    fn illegal_opcode_locks_up() {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let mut emu = Emulator::new(sender);
+        emu.skip_boot();
+        let mut data = vec![0x00; 256];
+        data[0] = 0xFB; // EI
+        data[2] = 0xD3;
+        emu.insert_boot_rom(BootRom::with_model(data, Model::Dmg).unwrap());
+        for _ in 0..3 {
+            emu.step_recorded();
+        }
+        assert_eq!(emu.cpu.locked_up(), Some(0x0002));
+
+        // Pending interrupts are not serviced, and time goes on.
+        emu.hw.write(0xFFFF, 0x04);
+        emu.hw.write(0xFF0F, 0x04);
+        assert_eq!(emu.step_recorded(), [Access::Idle]);
+        assert_eq!(emu.cpu.regs().prog_counter, 0x0003);
     }
 }
@@ -0,0 +1,421 @@
+//! The `debugger` module implements an interactive command-line debugger.
+//! It reads commands line by line, so it can be driven by a terminal as well as by a script.
+//! Type `help` at the prompt for the list of commands.
+
+use std::{
+    io::{self, BufRead, Write},
+    ops::RangeInclusive,
+};
+
+use crate::{
+    emulator::Emulator,
+    hardware::{disasm, Access, Bus, Registers},
+};
+
+const HELP: &str = "\
+Commands:
+  s, step [N]                   execute N instructions (default 1)
+  c, continue                   run until a breakpoint or watchpoint is hit
+  b, break ADDR                 stop before executing the instruction at ADDR
+  w, watch [r|w|rw] ADDR[-END]  stop after an instruction accesses ADDR..=END (default rw)
+  d, delete                     remove all breakpoints and watchpoints
+  l, list                       list breakpoints and watchpoints
+  r, regs                       print registers and flags
+  x ADDR [LEN]                  hex-dump LEN bytes starting at ADDR (default 64)
+  u, disasm [N]                 disassemble N instructions around PC (default 10)
+  h, help                       print this message
+  q, quit                       exit the debugger
+Addresses are hexadecimal, optionally prefixed by `$` or `0x`. Counts are decimal.";
+
+/// How many bytes before PC are searched for the start of an instruction, when disassembling.
+const MAX_LOOKBEHIND: u16 = 16;
+
+/// A range of memory to watch for accesses.
+pub(super) struct Watchpoint {
+    pub(super) range: RangeInclusive<u16>,
+    pub(super) read: bool,
+    pub(super) write: bool,
+}
+
+impl Watchpoint {
+    pub(super) fn matches(&self, access: &Access) -> bool {
+        match *access {
+            Access::Read(addr, _) => self.read && self.range.contains(&addr),
+            Access::Write(addr, _) => self.write && self.range.contains(&addr),
+            Access::Idle => false,
+        }
+    }
+}
+
+/// Why execution stopped.
+enum Stop {
+    Breakpoint(u16),
+    Watchpoint(Access),
+    /// The CPU executed the illegal opcode at this address, and hung.
+    LockedUp(u16),
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Reads and executes commands from `input` until it ends or `quit` is entered.
+    pub fn run(
+        &mut self,
+        emu: &mut Emulator,
+        input: impl BufRead,
+        mut output: impl Write,
+    ) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            write!(output, "(debug) ")?;
+            output.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(());
+            };
+            let args: Vec<&str> = line.split_whitespace().collect();
+            let Some((&cmd, args)) = args.split_first() else {
+                continue;
+            };
+            match self.execute(emu, cmd, args, &mut output) {
+                Ok(true) => (),
+                Ok(false) => return Ok(()),
+                Err(CommandError::Io(err)) => return Err(err),
+                Err(CommandError::Usage(msg)) => writeln!(output, "{msg}")?,
+            }
+        }
+    }
+
+    /// Executes a single command, and returns whether the debugger should keep running.
+    fn execute(
+        &mut self,
+        emu: &mut Emulator,
+        cmd: &str,
+        args: &[&str],
+        out: &mut impl Write,
+    ) -> Result<bool, CommandError> {
+        match cmd {
+            "s" | "step" => {
+                let count = args.first().map_or(Ok(1), |arg| parse_count(arg))?;
+                if let Some(stop) = self.resume(emu, Some(count)) {
+                    report(out, &stop)?;
+                }
+                print_current(out, emu)?;
+            }
+            "c" | "continue" => {
+                if let Some(stop) = self.resume(emu, None) {
+                    report(out, &stop)?;
+                }
+                print_current(out, emu)?;
+            }
+            "b" | "break" => {
+                let addr = parse_addr(args.first().ok_or(usage("break ADDR"))?)?;
+                self.breakpoints.push(addr);
+            }
+            "w" | "watch" => {
+                let (kind, range) = match args {
+                    [range] => ("rw", *range),
+                    [kind, range] => (*kind, *range),
+                    _ => return Err(usage("watch [r|w|rw] ADDR[-END]")),
+                };
+                let (read, write) = match kind {
+                    "r" => (true, false),
+                    "w" => (false, true),
+                    "rw" => (true, true),
+                    _ => return Err(usage("watch [r|w|rw] ADDR[-END]")),
+                };
+                let range = match range.split_once('-') {
+                    Some((start, end)) => parse_addr(start)?..=parse_addr(end)?,
+                    None => parse_addr(range).map(|addr| addr..=addr)?,
+                };
+                self.watchpoints.push(Watchpoint { range, read, write });
+            }
+            "d" | "delete" => {
+                self.breakpoints.clear();
+                self.watchpoints.clear();
+            }
+            "l" | "list" => {
+                for addr in &self.breakpoints {
+                    writeln!(out, "break {addr:04X}")?;
+                }
+                for watch in &self.watchpoints {
+                    let kind = match (watch.read, watch.write) {
+                        (true, false) => "r",
+                        (false, true) => "w",
+                        _ => "rw",
+                    };
+                    let (start, end) = watch.range.clone().into_inner();
+                    writeln!(out, "watch {kind} {start:04X}-{end:04X}")?;
+                }
+            }
+            "r" | "regs" => print_regs(out, emu.cpu.regs())?,
+            "x" => {
+                let start = parse_addr(args.first().ok_or(usage("x ADDR [LEN]"))?)?;
+                let len = args.get(1).map_or(Ok(64), |arg| parse_count(arg))?;
+                hexdump(out, &emu.hw, start, len)?;
+            }
+            "u" | "disasm" => {
+                let count = args.first().map_or(Ok(10), |arg| parse_count(arg))?;
+                disassemble_around(out, emu, count)?;
+            }
+            "h" | "help" => writeln!(out, "{HELP}")?,
+            "q" | "quit" => return Ok(false),
+            _ => {
+                return Err(CommandError::Usage(format!(
+                    "unknown command: {cmd}, type `help` for a list"
+                )))
+            }
+        }
+        Ok(true)
+    }
+
+    /// Executes up to `limit` instructions, or forever if `None`,
+    /// stopping early if a breakpoint or watchpoint is hit.
+    fn resume(&self, emu: &mut Emulator, limit: Option<u16>) -> Option<Stop> {
+        if limit == Some(0) {
+            return None;
+        }
+        let mut executed = 0;
+        loop {
+            // The first instruction is executed even if it has a breakpoint, or we would never move.
+            if let Some(stop) = self.step(emu) {
+                return Some(stop);
+            }
+            executed += 1;
+            if limit.is_some_and(|limit| executed >= limit) {
+                return None;
+            }
+            if let Some(stop) = self.hit_breakpoint(emu) {
+                return Some(stop);
+            }
+        }
+    }
+
+    /// Executes one instruction, and returns whether a watchpoint was hit while doing it,
+    /// or whether the CPU is hung.
+    fn step(&self, emu: &mut Emulator) -> Option<Stop> {
+        emu.step_recorded()
+            .into_iter()
+            .find(|access| self.watchpoints.iter().any(|watch| watch.matches(access)))
+            .map(Stop::Watchpoint)
+            .or_else(|| emu.cpu.locked_up().map(Stop::LockedUp))
+    }
+
+    fn hit_breakpoint(&self, emu: &Emulator) -> Option<Stop> {
+        let pc = emu.cpu.regs().prog_counter;
+        self.breakpoints
+            .contains(&pc)
+            .then_some(Stop::Breakpoint(pc))
+    }
+}
+
+enum CommandError {
+    Io(io::Error),
+    /// The command was malformed. It holds a message for the user.
+    Usage(String),
+}
+
+impl From<io::Error> for CommandError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+fn usage(msg: &str) -> CommandError {
+    CommandError::Usage(format!("usage: {msg}"))
+}
+
+fn parse_addr(arg: &str) -> Result<u16, CommandError> {
+    let digits = arg
+        .strip_prefix('$')
+        .or_else(|| arg.strip_prefix("0x"))
+        .unwrap_or(arg);
+    u16::from_str_radix(digits, 16)
+        .map_err(|_| CommandError::Usage(format!("invalid address: {arg}")))
+}
+
+fn parse_count(arg: &str) -> Result<u16, CommandError> {
+    arg.parse()
+        .map_err(|_| CommandError::Usage(format!("invalid count: {arg}")))
+}
+
+fn report(out: &mut impl Write, stop: &Stop) -> io::Result<()> {
+    match stop {
+        Stop::Breakpoint(addr) => writeln!(out, "breakpoint at {addr:04X}"),
+        Stop::Watchpoint(Access::Read(addr, val)) => {
+            writeln!(out, "watchpoint: read {val:02X} from {addr:04X}")
+        }
+        Stop::Watchpoint(Access::Write(addr, val)) => {
+            writeln!(out, "watchpoint: wrote {val:02X} to {addr:04X}")
+        }
+        Stop::Watchpoint(Access::Idle) => unreachable!(),
+        Stop::LockedUp(addr) => writeln!(out, "CPU locked up by illegal opcode at {addr:04X}"),
+    }
+}
+
+/// Prints the instruction about to be executed.
+fn print_current(out: &mut impl Write, emu: &Emulator) -> io::Result<()> {
+    let pc = emu.cpu.regs().prog_counter;
+    print_instruction(out, &disasm::decode(pc, |addr| emu.hw.peek(addr)), true)
+}
+
+fn print_instruction(
+    out: &mut impl Write,
+    instr: &disasm::Instruction,
+    current: bool,
+) -> io::Result<()> {
+    let bytes: Vec<String> = instr.bytes.iter().map(|b| format!("{b:02X}")).collect();
+    writeln!(
+        out,
+        "{} {:04X}: {:<9} {instr}",
+        if current { "=>" } else { "  " },
+        instr.addr,
+        bytes.join(" ")
+    )
+}
+
+fn print_regs(out: &mut impl Write, regs: &Registers) -> io::Result<()> {
+    let flag = |set: bool, name: char| if set { name } else { '-' };
+    writeln!(
+        out,
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} [{}{}{}{}]",
+        regs.a,
+        u8::from(regs.flags),
+        regs.b,
+        regs.c,
+        regs.d,
+        regs.e,
+        regs.h,
+        regs.l,
+        regs.stack_pointer,
+        regs.prog_counter,
+        flag(regs.flags.zero, 'Z'),
+        flag(regs.flags.neg, 'N'),
+        flag(regs.flags.half_carry, 'H'),
+        flag(regs.flags.carry, 'C'),
+    )
+}
+
+fn hexdump(out: &mut impl Write, bus: &impl Bus, start: u16, len: u16) -> io::Result<()> {
+    const ROW: u16 = 16;
+    let mut offset = 0;
+    while offset < len {
+        let row_start = start.wrapping_add(offset);
+        let row_len = ROW.min(len - offset);
+        let bytes: Vec<u8> = (0..row_len)
+            .map(|i| bus.peek(row_start.wrapping_add(i)))
+            .collect();
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+            .collect();
+        writeln!(out, "{row_start:04X}: {:<47}  {ascii}", hex.join(" "))?;
+        offset += row_len;
+    }
+    Ok(())
+}
+
+/// Disassembles `count` instructions, the current one being roughly in the middle.
+/// Instructions have variable length, so the ones before PC are found by looking for
+/// the farthest address from which decoding lands exactly on PC.
+fn disassemble_around(out: &mut impl Write, emu: &Emulator, count: u16) -> io::Result<()> {
+    let pc = emu.cpu.regs().prog_counter;
+    let read = |addr| emu.hw.peek(addr);
+    let lands_on_pc = |start: u16| {
+        let mut addr = start;
+        while addr != pc && pc.wrapping_sub(addr) <= MAX_LOOKBEHIND {
+            addr = addr.wrapping_add(disasm::decode(addr, read).size());
+        }
+        addr == pc
+    };
+    let start = (1..=MAX_LOOKBEHIND)
+        .rev()
+        .map(|back| pc.wrapping_sub(back))
+        .find(|&start| lands_on_pc(start))
+        .unwrap_or(pc);
+
+    let mut before = Vec::new();
+    let mut addr = start;
+    while addr != pc {
+        let instr = disasm::decode(addr, read);
+        addr = addr.wrapping_add(instr.size());
+        before.push(instr);
+    }
+    let skip = before.len().saturating_sub(count as usize / 2);
+    for instr in &before[skip..] {
+        print_instruction(out, instr, false)?;
+    }
+
+    let mut addr = pc;
+    for _ in 0..count as usize - (before.len() - skip).min(count as usize) {
+        let instr = disasm::decode(addr, read);
+        print_instruction(out, &instr, addr == pc)?;
+        addr = addr.wrapping_add(instr.size());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::hardware::{BootRom, Model};
+
+    /// Runs a script on a boot ROM, and returns the output without prompts.
+    fn session(boot_rom: Vec<u8>, script: &str) -> String {
+        let (sender, _receiver) = mpsc::sync_channel(1);
+        let mut emu = Emulator::new(sender);
+        emu.insert_boot_rom(BootRom::with_model(boot_rom, Model::Dmg).unwrap());
+        let mut output = Vec::new();
+        Debugger::new()
+            .run(&mut emu, io::Cursor::new(script), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap().replace("(debug) ", "")
+    }
+
+    #[test]
+    fn script() {
+        let mut boot_rom = vec![0x00; 256];
+        boot_rom[0x10..0x19].copy_from_slice(&[
+            0x3E, 0x42, // LD A,$42
+            0xEA, 0x00, 0xC0, // LD ($C000),A
+            0x00, // NOP
+            0xFA, 0x00, 0xC0, // LD A,($C000)
+        ]);
+        let output = session(
+            boot_rom,
+            "s 0\ns 16\nb 16\nw r C000-C0FF\nl\nc\nc\nu 4\ns 2\nx C000 4\nfoo\n",
+        );
+        let expected = [
+            // Stepping 0 instructions does nothing.
+            "=> 0000: 00        NOP",
+            "=> 0010: 3E 42     LD A, $42",
+            "break 0016",
+            "watch r C000-C0FF",
+            // The write to $C000 is not watched, the read is.
+            "breakpoint at 0016",
+            "=> 0016: FA 00 C0  LD A, ($C000)",
+            "watchpoint: read 42 from C000",
+            "=> 0019: 00        NOP",
+            // Decoding backwards lands on the start of the 3-byte instruction.
+            "   0015: 00        NOP",
+            "   0016: FA 00 C0  LD A, ($C000)",
+            "=> 0019: 00        NOP",
+            "   001A: 00        NOP",
+            "=> 001B: 00        NOP",
+            "C000: 42 00 00 00                                      B...",
+            "unknown command: foo, type `help` for a list",
+            "",
+        ];
+        assert_eq!(output, expected.join("\n"));
+    }
+}
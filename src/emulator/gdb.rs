@@ -0,0 +1,458 @@
+//! The `gdb` module implements a stub for GDB's remote serial protocol (RSP),
+//! so that GDB, or any other RSP client, can debug programs running in the emulator.
+//!
+//! Registers are exchanged as 16-bit little-endian values, in this order:
+//! AF, BC, DE, HL, SP and PC.
+//!
+//! See <https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html>
+
+use std::{
+    io::{self, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{
+    emulator::{debugger::Watchpoint, Emulator},
+    hardware::{Access, Bus, Registers},
+};
+
+/// Instructions executed between two checks for an interruption by the client.
+const INTERRUPT_POLL_INTERVAL: u32 = 1024;
+
+/// Signal reported when the program stops for a breakpoint or a step.
+const SIGTRAP: u8 = 5;
+/// Signal reported when the program stops because the client asked so.
+const SIGINT: u8 = 2;
+/// Signal reported when the CPU is hung by an illegal opcode.
+const SIGILL: u8 = 4;
+
+/// Byte the client sends to interrupt a running program.
+const INTERRUPT: u8 = 0x03;
+
+/// Listens on `addr`, waits for a client to connect and serves it until it detaches.
+pub fn serve(emu: &mut Emulator, addr: impl ToSocketAddrs) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+    GdbStub::new().run(emu, stream)
+}
+
+/// Why execution stopped.
+enum Stop {
+    Signal(u8),
+    Watchpoint(Access, &'static str),
+}
+
+#[derive(Default)]
+pub struct GdbStub {
+    breakpoints: Vec<u16>,
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Serves a connected client until it detaches, kills the program or disconnects.
+    pub fn run(&mut self, emu: &mut Emulator, stream: TcpStream) -> io::Result<()> {
+        // Packets are small and exchanged one at a time: don't wait to coalesce them.
+        stream.set_nodelay(true)?;
+        let mut conn = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            stream,
+        };
+        loop {
+            let Some(packet) = conn.receive()? else {
+                return Ok(());
+            };
+            let Some(reply) = self.handle(emu, &packet, &mut conn)? else {
+                return Ok(());
+            };
+            conn.send(&reply)?;
+        }
+    }
+
+    /// Handles a packet, and returns the reply to send back,
+    /// or `None` if the session is over.
+    fn handle(
+        &mut self,
+        emu: &mut Emulator,
+        packet: &str,
+        conn: &mut Connection,
+    ) -> io::Result<Option<String>> {
+        let (cmd, args) = packet.split_at(packet.len().min(1));
+        let reply = match cmd {
+            "?" => stop_reply(&Stop::Signal(SIGTRAP)),
+            "g" => {
+                let regs = emu.cpu.regs();
+                register_values(regs).map(encode_u16).concat()
+            }
+            "G" => match decode_hex(args) {
+                Some(bytes) if bytes.len() == 2 * REGISTERS => {
+                    let regs = emu.cpu.regs_mut();
+                    for (idx, val) in bytes.chunks(2).enumerate() {
+                        set_register(regs, idx, u16::from_le_bytes([val[0], val[1]]));
+                    }
+                    "OK".to_string()
+                }
+                _ => error(),
+            },
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(idx) if idx < REGISTERS => encode_u16(register_values(emu.cpu.regs())[idx]),
+                _ => error(),
+            },
+            "P" => {
+                let parsed = args.split_once('=').and_then(|(idx, val)| {
+                    let idx = usize::from_str_radix(idx, 16).ok()?;
+                    let val = decode_hex(val)?;
+                    (idx < REGISTERS && val.len() == 2).then(|| (idx, [val[0], val[1]]))
+                });
+                match parsed {
+                    Some((idx, val)) => {
+                        set_register(emu.cpu.regs_mut(), idx, u16::from_le_bytes(val));
+                        "OK".to_string()
+                    }
+                    None => error(),
+                }
+            }
+            "m" => match parse_addr_len(args) {
+                Some((addr, len)) => (0..len)
+                    .map(|i| format!("{:02x}", emu.hw.peek(addr.wrapping_add(i))))
+                    .collect(),
+                None => error(),
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_addr_len(range)?;
+                    let data = decode_hex(data)?;
+                    (data.len() == len as usize).then_some((addr, data))
+                });
+                match parsed {
+                    Some((addr, data)) => {
+                        for (i, val) in data.into_iter().enumerate() {
+                            emu.hw.write(addr.wrapping_add(i as u16), val);
+                        }
+                        "OK".to_string()
+                    }
+                    None => error(),
+                }
+            }
+            "s" | "c" => {
+                if let Ok(addr) = u16::from_str_radix(args, 16) {
+                    emu.cpu.regs_mut().prog_counter = addr;
+                }
+                let stop = if cmd == "s" {
+                    self.step(emu).unwrap_or(Stop::Signal(SIGTRAP))
+                } else {
+                    self.resume(emu, conn)?
+                };
+                stop_reply(&stop)
+            }
+            "Z" | "z" => match self.set_point(cmd == "Z", args) {
+                Some(()) => "OK".to_string(),
+                None => String::new(),
+            },
+            "H" => "OK".to_string(),
+            "q" if args.starts_with("Supported") => "PacketSize=1000".to_string(),
+            "q" if args == "Attached" => "1".to_string(),
+            "D" => {
+                conn.send("OK")?;
+                return Ok(None);
+            }
+            "k" => return Ok(None),
+            // An empty reply tells the client the packet is not supported.
+            _ => String::new(),
+        };
+        Ok(Some(reply))
+    }
+
+    /// Inserts or removes a breakpoint or watchpoint, as in `Z` and `z` packets.
+    /// Returns `None` if the kind of point is not supported.
+    fn set_point(&mut self, insert: bool, args: &str) -> Option<()> {
+        let mut fields = args.split(',');
+        let kind = fields.next()?;
+        let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let len = u16::from_str_radix(fields.next()?, 16).ok()?;
+        if kind == "0" || kind == "1" {
+            if insert {
+                self.breakpoints.push(addr);
+            } else {
+                self.breakpoints.retain(|&bp| bp != addr);
+            }
+            return Some(());
+        }
+        let (read, write) = match kind {
+            "2" => (false, true),
+            "3" => (true, false),
+            "4" => (true, true),
+            _ => return None,
+        };
+        let range = addr..=addr.saturating_add(len.max(1) - 1);
+        if insert {
+            self.watchpoints.push(Watchpoint { range, read, write });
+        } else {
+            self.watchpoints.retain(|watch| {
+                (watch.range.clone(), watch.read, watch.write) != (range.clone(), read, write)
+            });
+        }
+        Some(())
+    }
+
+    /// Executes instructions until a breakpoint or watchpoint is hit, or the client interrupts.
+    fn resume(&self, emu: &mut Emulator, conn: &mut Connection) -> io::Result<Stop> {
+        let mut executed: u32 = 0;
+        loop {
+            // The first instruction is executed even if it has a breakpoint, or we would never move.
+            if let Some(stop) = self.step(emu) {
+                return Ok(stop);
+            }
+            if self.breakpoints.contains(&emu.cpu.regs().prog_counter) {
+                return Ok(Stop::Signal(SIGTRAP));
+            }
+            executed = executed.wrapping_add(1);
+            if executed.is_multiple_of(INTERRUPT_POLL_INTERVAL) && conn.interrupted()? {
+                return Ok(Stop::Signal(SIGINT));
+            }
+        }
+    }
+
+    /// Executes one instruction, and returns whether a watchpoint was hit while doing it,
+    /// or whether the CPU is hung.
+    fn step(&self, emu: &mut Emulator) -> Option<Stop> {
+        emu.step_recorded()
+            .into_iter()
+            .find_map(|access| {
+                let watch = self
+                    .watchpoints
+                    .iter()
+                    .find(|watch| watch.matches(&access))?;
+                let kind = match (watch.read, watch.write) {
+                    (false, true) => "watch",
+                    (true, false) => "rwatch",
+                    _ => "awatch",
+                };
+                Some(Stop::Watchpoint(access, kind))
+            })
+            .or_else(|| emu.cpu.locked_up().map(|_| Stop::Signal(SIGILL)))
+    }
+}
+
+/// Number of registers exchanged with the client.
+const REGISTERS: usize = 6;
+
+fn register_values(regs: &Registers) -> [u16; REGISTERS] {
+    [
+        u16::from_be_bytes([regs.a, regs.flags.into()]),
+        u16::from_be_bytes([regs.b, regs.c]),
+        u16::from_be_bytes([regs.d, regs.e]),
+        u16::from_be_bytes([regs.h, regs.l]),
+        regs.stack_pointer,
+        regs.prog_counter,
+    ]
+}
+
+fn set_register(regs: &mut Registers, idx: usize, val: u16) {
+    let [high, low] = val.to_be_bytes();
+    match idx {
+        0 => (regs.a, regs.flags) = (high, low.into()),
+        1 => (regs.b, regs.c) = (high, low),
+        2 => (regs.d, regs.e) = (high, low),
+        3 => (regs.h, regs.l) = (high, low),
+        4 => regs.stack_pointer = val,
+        5 => regs.prog_counter = val,
+        _ => unreachable!(),
+    }
+}
+
+fn stop_reply(stop: &Stop) -> String {
+    match stop {
+        Stop::Signal(signal) => format!("S{signal:02x}"),
+        Stop::Watchpoint(Access::Read(addr, _) | Access::Write(addr, _), kind) => {
+            format!("T{SIGTRAP:02x}{kind}:{addr:x};")
+        }
+        Stop::Watchpoint(Access::Idle, _) => unreachable!(),
+    }
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn encode_u16(val: u16) -> String {
+    let [low, high] = val.to_le_bytes();
+    format!("{low:02x}{high:02x}")
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses the `ADDR,LEN` arguments of memory packets.
+fn parse_addr_len(args: &str) -> Option<(u16, u16)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u16::from_str_radix(addr, 16).ok()?,
+        u16::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// Sends and receives packets, taking care of checksums and acknowledgments.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+impl Connection {
+    /// Waits for the next valid packet, and returns its content.
+    /// Returns `None` if the client disconnected.
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Skip anything before the packet start, such as acknowledgments.
+            let mut byte = [0];
+            loop {
+                if self.reader.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                if self.reader.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                .is_some_and(|checksum| checksum == compute_checksum(&data));
+            if !valid {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            self.stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let checksum = compute_checksum(data.as_bytes());
+        write!(self.stream, "${data}#{checksum:02x}")?;
+        self.stream.flush()
+    }
+
+    /// Returns whether the client sent an interruption, without blocking.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        if !self.reader.buffer().is_empty() {
+            let mut byte = [0];
+            self.reader.read_exact(&mut byte)?;
+            return Ok(byte[0] == INTERRUPT);
+        }
+        self.stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let read = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+        match read {
+            Ok(len) => Ok(len == 1 && byte[0] == INTERRUPT),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn compute_checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread};
+
+    use super::*;
+    use crate::hardware::{BootRom, Model};
+
+    /// A minimal RSP client, which sends a packet and returns the reply.
+    fn exchange(stream: &mut TcpStream, data: &str) -> String {
+        write!(stream, "${data}#{:02x}", compute_checksum(data.as_bytes())).unwrap();
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            match byte[0] {
+                b'+' if reply.is_empty() => (),
+                b'#' => break,
+                _ => reply.push(byte[0]),
+            }
+        }
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum).unwrap();
+        stream.write_all(b"+").unwrap();
+        String::from_utf8(reply[1..].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.set_nodelay(true).unwrap();
+            let replies = [
+                "?",
+                "P5=1000",
+                "p5",
+                "G000000000000000000000000",
+                "g",
+                "m0,4",
+                "Z0,10,1",
+                "c",
+                "s",
+                "Z3,20,1",
+                "c",
+                "vMustReplyEmpty",
+            ]
+            .map(|packet| exchange(&mut stream, packet));
+            write!(stream, "$k#{:02x}", compute_checksum(b"k")).unwrap();
+            replies
+        });
+
+        let (audio_sender, _) = mpsc::sync_channel(1);
+        let mut emu = Emulator::new(audio_sender);
+        // NOPs all along, whatever the embedded boot ROM is.
+        emu.insert_boot_rom(BootRom::with_model(vec![0; 256], Model::Dmg).unwrap());
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new().run(&mut emu, stream).unwrap();
+
+        assert_eq!(
+            client.join().unwrap(),
+            [
+                "S05",
+                "OK",
+                "1000",
+                "OK",
+                "000000000000000000000000",
+                "00000000",
+                "OK",
+                "S05",
+                "S05",
+                "OK",
+                "T05rwatch:20;",
+                "",
+            ]
+        );
+        assert_eq!(emu.cpu.regs().prog_counter, 0x21);
+    }
+}
@@ -1,153 +1,397 @@
 pub mod apu;
 pub mod keypad;
 
+mod bootrom;
+mod bus;
 mod cartridge;
 mod cpu;
+mod dma;
 mod gpu;
+mod interrupts;
+mod serial;
 mod timer;
+mod wram;
 
-use std::sync::mpsc;
+use std::{io, sync::mpsc};
 
 use crate::hardware::apu::Apu;
-pub use crate::hardware::cartridge::Cartridge;
-pub use crate::hardware::cpu::Cpu;
+pub use crate::hardware::bootrom::{BootRom, BootRomError, Model};
+pub use crate::hardware::bus::{Access, Bus, Recorder};
+pub use crate::hardware::cartridge::{
+    Cartridge, FileStorage, Frame, ImageSource, Infrared, SaveStorage,
+};
+pub use crate::hardware::cpu::disasm;
+pub use crate::hardware::cpu::{Cpu, Flags, Registers};
 
+use crate::hardware::dma::{Conflict, Dma};
 use crate::hardware::gpu::Gpu;
+pub use crate::hardware::interrupts::Interrupt;
+use crate::hardware::interrupts::Interrupts;
 use crate::hardware::keypad::Keypad;
+use crate::hardware::serial::Serial;
 use crate::hardware::timer::Timer;
+use crate::hardware::wram::{self as work_ram, WorkRam};
 
 /// Master clock for all hardware.
 /// Some components may run at a submultiple of this frequency, though.
 pub const MASTER_CLOCK: u32 = 4 * 1024 * 1024;
 
-const BOOTROM: &[u8; 256] = include_bytes!("../bootrom/bin/dmg.bin");
+/// Clock ticks in a machine cycle (M-cycle), that is, the time the CPU takes to access memory once.
+pub const M_CYCLE: u8 = 4;
+
+/// The DMG boot ROM, built from the `bootrom` submodule.
+/// It is not an official dump, so it cannot be identified by its hash.
+#[cfg(feature = "bootrom")]
+const BOOTROM: Option<&[u8; 256]> = Some(include_bytes!("../bootrom/bin/dmg.bin"));
+#[cfg(not(feature = "bootrom"))]
+const BOOTROM: Option<&[u8; 256]> = None;
+
+/// Value read from addresses nothing drives, such as unmapped I/O registers
+/// or a missing cartridge: the data bus is pulled up, so every bit reads as 1.
+const OPEN_BUS: u8 = 0xFF;
 
 pub struct Hardware {
-    work_ram: [u8; (WORK_RAM_END - WORK_RAM_START + 1) as usize],
-    echo_ram: [u8; (ECHO_RAM_END - ECHO_RAM_START + 1) as usize],
+    /// The boot ROM, as long as it is mapped over the start of the cartridge ROM.
+    boot_rom: Option<BootRom>,
+    /// The model being emulated, which determines the state after boot.
+    model: Model,
+    work_ram: WorkRam,
+    high_ram: [u8; (HIGH_RAM_END - HIGH_RAM_START + 1) as usize],
 
     pub apu: Apu,
+    dma: Dma,
     gpu: Gpu,
     cartrdige: Option<Cartridge>,
+    interrupts: Interrupts,
     pub keypad: Keypad,
+    serial: Serial,
     pub timer: Timer,
 }
 
 impl Hardware {
     pub fn new(audio_buffer: mpsc::SyncSender<(u8, u8)>) -> Self {
         Self {
-            work_ram: [0; (WORK_RAM_END - WORK_RAM_START + 1) as usize],
-            echo_ram: [0; (ECHO_RAM_END - ECHO_RAM_START + 1) as usize],
+            boot_rom: BOOTROM.map(|data| BootRom::embedded(data, Model::Dmg)),
+            model: Model::Dmg,
+            work_ram: WorkRam::new(work_ram::DMG_BANKS),
+            high_ram: [0; (HIGH_RAM_END - HIGH_RAM_START + 1) as usize],
 
             apu: Apu::new(audio_buffer),
+            dma: Dma::new(),
             gpu: Gpu::new(),
             cartrdige: None,
+            interrupts: Interrupts::new(),
             keypad: Keypad::new(),
+            serial: Serial::new(),
             timer: Default::default(),
         }
     }
 
+    /// Reads a byte as the CPU sees it, which may differ from memory during OAM DMA.
     pub fn read(&self, addr: u16) -> u8 {
+        match self.dma.conflict(addr) {
+            Some(Conflict::Oam) => OPEN_BUS,
+            Some(Conflict::Bus(source)) => self.read_direct(source),
+            None => self.read_direct(addr),
+        }
+    }
+
+    /// Writes a byte as the CPU does, which has no effect on memory taken by OAM DMA.
+    pub fn write(&mut self, addr: u16, val: u8) {
+        if self.dma.conflict(addr).is_none() {
+            self.write_direct(addr, val);
+        }
+    }
+
+    /// Reads a byte straight from the memory map, regardless of OAM DMA.
+    fn read_direct(&self, addr: u16) -> u8 {
+        if let Some(boot_rom) = self
+            .boot_rom
+            .as_ref()
+            .filter(|_| (BOOTROM_START..=BOOTROM_END).contains(&addr))
+        {
+            return boot_rom.read(addr);
+        }
         match addr {
-            BOOTROM_START..=BOOTROM_END => BOOTROM[addr as usize],
+            CARTRIDGE_ROM_START..=CARTRIDGE_ROM_END | EXTERNAL_RAM_START..=EXTERNAL_RAM_END => self
+                .cartrdige
+                .as_ref()
+                .map_or(OPEN_BUS, |cart| cart.read(addr)),
             VIDEO_RAM_START..=VIDEO_RAM_END => self.gpu.read_vram(addr - VIDEO_RAM_START),
-            WORK_RAM_START..=WORK_RAM_END => self.work_ram[(addr - WORK_RAM_START) as usize],
-            ECHO_RAM_START..=ECHO_RAM_END => self.echo_ram[(addr - ECHO_RAM_START) as usize],
-            OAM_RAM_START..=OAM_RAM_END => self.gpu.read_oam(addr),
+            WORK_RAM_START..=WORK_RAM_END => self.work_ram.read(addr - WORK_RAM_START),
+            ECHO_RAM_START..=ECHO_RAM_END => self.work_ram.read(addr - ECHO_RAM_START),
+            OAM_RAM_START..=OAM_RAM_END => self.gpu.read_oam(addr - OAM_RAM_START),
+            // On DMG, this region reads as zero, at least while OAM is accessible.
+            UNUSABLE_START..=UNUSABLE_END => 0x00,
 
             MAPPED_KEYPAD_START..=MAPPED_KEYPAD_END => self
                 .keypad
                 .read_register((addr - MAPPED_KEYPAD_START) as usize),
+            MAPPED_SERIAL_START..=MAPPED_SERIAL_END => self
+                .serial
+                .read_register((addr - MAPPED_SERIAL_START) as usize),
             MAPPED_TIMER_START..=MAPPED_TIMER_END => self
                 .timer
                 .read_register((addr - MAPPED_TIMER_START) as usize),
+            MAPPED_INTERRUPT_FLAG => self.interrupts.read_requested(),
             APU_REGISTERS_START..=APU_REGISTERS_END => self
                 .apu
                 .read_register((addr - APU_REGISTERS_START) as usize),
-            INTERRUPTS_START..=INTERRUPTS_END => self.read_interrupts(),
-            _ => unreachable!(),
+            MAPPED_DMA => self.dma.read_register(),
+            MAPPED_BOOTROM_UNMAP => OPEN_BUS,
+            LCD_REGISTERS_START..=LCD_REGISTERS_END => self
+                .gpu
+                .read_register((addr - LCD_REGISTERS_START) as usize),
+            HIGH_RAM_START..=HIGH_RAM_END => self.high_ram[(addr - HIGH_RAM_START) as usize],
+            INTERRUPTS_START..=INTERRUPTS_END => self.interrupts.read_enabled(),
+            MAPPED_SVBK if self.work_ram.switchable() => self.work_ram.read_register(),
+            // I/O registers not backed by any DMG hardware.
+            MAPPED_SVBK
+            | 0xFF03
+            | 0xFF08..=0xFF0E
+            | 0xFF4C..=0xFF4F
+            | 0xFF51..=0xFF6F
+            | 0xFF71..=0xFF7F => OPEN_BUS,
         }
     }
 
-    pub fn write(&mut self, addr: u16, val: u8) {
+    fn write_direct(&mut self, addr: u16, val: u8) {
         match addr {
-            VIDEO_RAM_START..=VIDEO_RAM_END => self.gpu.write_vram(addr, val),
-            WORK_RAM_START..=WORK_RAM_END => self.work_ram[(addr - WORK_RAM_START) as usize] = val,
-            ECHO_RAM_START..=ECHO_RAM_END => self.echo_ram[(addr - ECHO_RAM_START) as usize] = val,
-            OAM_RAM_START..=OAM_RAM_END => self.gpu.write_oam(addr, val),
+            CARTRIDGE_ROM_START..=CARTRIDGE_ROM_END | EXTERNAL_RAM_START..=EXTERNAL_RAM_END => {
+                if let Some(cart) = self.cartrdige.as_mut() {
+                    cart.write(addr, val);
+                }
+            }
+            VIDEO_RAM_START..=VIDEO_RAM_END => self.gpu.write_vram(addr - VIDEO_RAM_START, val),
+            WORK_RAM_START..=WORK_RAM_END => self.work_ram.write(addr - WORK_RAM_START, val),
+            ECHO_RAM_START..=ECHO_RAM_END => self.work_ram.write(addr - ECHO_RAM_START, val),
+            OAM_RAM_START..=OAM_RAM_END => self.gpu.write_oam(addr - OAM_RAM_START, val),
+            UNUSABLE_START..=UNUSABLE_END => (),
 
             MAPPED_KEYPAD_START..=MAPPED_KEYPAD_END => self
                 .keypad
                 .write_register((addr - MAPPED_KEYPAD_START) as usize, val),
+            MAPPED_SERIAL_START..=MAPPED_SERIAL_END => self
+                .serial
+                .write_register((addr - MAPPED_SERIAL_START) as usize, val),
             MAPPED_TIMER_START..=MAPPED_TIMER_END => self
                 .timer
                 .write_register((addr - MAPPED_TIMER_START) as usize, val),
+            MAPPED_INTERRUPT_FLAG => self.interrupts.write_requested(val),
             APU_REGISTERS_START..=APU_REGISTERS_END => self
                 .apu
                 .write_register((addr - APU_REGISTERS_START) as usize, val),
-            MAPPED_DMA => self.dma_write(val),
-            _ => todo!(),
+            MAPPED_DMA => self.dma.write_register(val),
+            // Once unmapped, the boot ROM can only come back with a reset.
+            MAPPED_BOOTROM_UNMAP if val != 0 => self.boot_rom = None,
+            MAPPED_BOOTROM_UNMAP => (),
+            LCD_REGISTERS_START..=LCD_REGISTERS_END => self
+                .gpu
+                .write_register((addr - LCD_REGISTERS_START) as usize, val),
+            HIGH_RAM_START..=HIGH_RAM_END => self.high_ram[(addr - HIGH_RAM_START) as usize] = val,
+            INTERRUPTS_START..=INTERRUPTS_END => self.interrupts.write_enabled(val),
+            MAPPED_SVBK if self.work_ram.switchable() => self.work_ram.write_register(val),
+            MAPPED_SVBK
+            | 0xFF03
+            | 0xFF08..=0xFF0E
+            | 0xFF4C..=0xFF4F
+            | 0xFF51..=0xFF6F
+            | 0xFF71..=0xFF7F => (),
+        }
+    }
+
+    /// Returns whether the boot ROM is still mapped, that is, the system is booting.
+    pub fn booting(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    /// Maps a boot ROM, replacing the current one, and emulates the model it belongs to.
+    /// It is mapped again even if the previous one was unmapped through 0xFF50.
+    pub fn insert_boot_rom(&mut self, boot_rom: BootRom) {
+        self.model = boot_rom.model();
+        self.boot_rom = Some(boot_rom);
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Sets the model to emulate, which determines the state [`Self::skip_boot`] leaves.
+    /// Inserting a boot ROM sets the model too.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    /// Unmaps the boot ROM and sets I/O registers to the values the boot ROM
+    /// of the current model leaves them with.
+    ///
+    /// See <https://gbdev.io/pandocs/Power_Up_Sequence.html#hardware-registers>
+    pub fn skip_boot(&mut self) {
+        self.boot_rom = None;
+        self.timer.set_divider(self.model.divider_after_boot());
+        // Writing DMA would start a transfer.
+        self.dma = Dma::with_source(0xFF);
+        // Audio registers that would trigger channels are left alone:
+        // they would play the boot sound again. Audio is left off too, instead of 0xF1,
+        // as the wave channel cannot play yet.
+        for (addr, val) in [
+            (0xFF00, 0xCF), // P1
+            (0xFF02, 0x7E), // SC
+            (0xFF07, 0xF8), // TAC
+            (0xFF0F, 0xE1), // IF
+            (0xFF10, 0x80), // NR10
+            (0xFF11, 0xBF), // NR11
+            (0xFF12, 0xF3), // NR12
+            (0xFF13, 0xFF), // NR13
+            (0xFF16, 0x3F), // NR21
+            (0xFF1A, 0x7F), // NR30
+            (0xFF1B, 0xFF), // NR31
+            (0xFF1C, 0x9F), // NR32
+            (0xFF1D, 0xFF), // NR33
+            (0xFF20, 0xFF), // NR41
+            (0xFF24, 0x77), // NR50
+            (0xFF25, 0xF3), // NR51
+            (0xFF26, 0x00), // NR52
+            (0xFF40, 0x91), // LCDC
+            (0xFF41, 0x85), // STAT
+            (0xFF47, 0xFC), // BGP
+        ] {
+            self.write(addr, val);
         }
-        todo!()
     }
 
     pub fn insert_cartridge(&mut self, cart: Cartridge) {
         self.cartrdige = Some(cart);
     }
 
-    fn read_interrupts(&self) -> u8 {
-        let mut byte: u8 = 0;
-        let ints: [Option<&dyn Interruptible>; 5] =
-            [None, None, Some(&self.timer), None, Some(&self.keypad)];
-        for (i, per) in ints.iter().enumerate() {
-            match per {
-                Some(per) => byte |= (per.has_interrupt() as u8) << i,
-                None => continue,
+    /// Saves the state of the cartridge, if any. See [`Cartridge::save`].
+    pub fn save(&mut self) -> io::Result<()> {
+        self.cartrdige.as_mut().map_or(Ok(()), Cartridge::save)
+    }
+
+    /// Returns the state of the rumble motor of the cartridge, if it changed since the last call.
+    pub fn take_rumble(&mut self) -> Option<bool> {
+        self.cartrdige.as_mut().and_then(Cartridge::take_rumble)
+    }
+
+    /// Tilts the accelerometer of the cartridge, if any. See [`Cartridge::set_tilt`].
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let Some(cart) = self.cartrdige.as_mut() {
+            cart.set_tilt(x, y);
+        }
+    }
+
+    /// Returns whether the cartridge asks to be saved. See [`Cartridge::save_requested`].
+    pub fn save_requested(&self) -> bool {
+        self.cartrdige
+            .as_ref()
+            .is_some_and(Cartridge::save_requested)
+    }
+
+    /// Advances the state of all peripherals by a number of clock ticks.
+    pub fn tick(&mut self, ticks: u8) {
+        self.timer.tick(ticks);
+        self.serial.tick(ticks);
+        self.gpu.tick(ticks);
+        self.apu.tick(ticks);
+        if let Some(cart) = self.cartrdige.as_mut() {
+            cart.tick(ticks);
+        }
+        for _ in 0..self.dma.tick(ticks) {
+            if let Some(source) = self.dma.step() {
+                // OAM is written directly, as DMA does not go through the CPU's view of memory.
+                let val = self.read_direct(source);
+                self.gpu.write_oam(source & 0xFF, val);
             }
         }
-        byte
+        self.collect_interrupts();
     }
 
-    // TODO: return 160 clock ticks.
-    fn dma_write(&mut self, addr: u8) {
-        // DMA copies 0xA0 bytes starting from address addr, but multiplied by 256.
-        let read_base = (addr as u16) << 8;
-        const WRITE_BASE: u16 = 0xFE00;
-        for i in 0..0xA0 {
-            self.write(WRITE_BASE + i, self.read(read_base + i))
+    /// Forwards interrupts raised by peripherals to the interrupt controller.
+    fn collect_interrupts(&mut self) {
+        let sources: [(Interrupt, &mut dyn Interruptible); 4] = [
+            (Interrupt::VBlank, &mut self.gpu),
+            (Interrupt::Timer, &mut self.timer),
+            (Interrupt::Serial, &mut self.serial),
+            (Interrupt::Joypad, &mut self.keypad),
+        ];
+        for (int, per) in sources {
+            if per.take_interrupt() {
+                self.interrupts.request(int);
+            }
+        }
+        // The GPU raises two interrupts, so STAT is taken on its own.
+        if self.gpu.take_stat_interrupt() {
+            self.interrupts.request(Interrupt::Stat);
         }
     }
 }
 
 pub trait Interruptible {
-    fn has_interrupt(&self) -> bool;
+    /// Returns whether the peripheral raised an interrupt since the last call.
+    fn take_interrupt(&mut self) -> bool;
 }
 
-const BOOTROM_START: u16 = 0x00;
-const BOOTROM_END: u16 = 0xFF;
+// Every address must be handled, which the compiler checks when matching on these ranges.
+// I/O registers not backed by any DMG hardware have no constants of their own.
+
+const BOOTROM_START: u16 = 0x0000;
+const BOOTROM_END: u16 = 0x00FF;
+
+/// The cartridge ROM. Its first 256 bytes are hidden while the boot ROM is mapped.
+const CARTRIDGE_ROM_START: u16 = 0x0000;
+const CARTRIDGE_ROM_END: u16 = 0x7FFF;
 
 const VIDEO_RAM_START: u16 = 0x8000;
 const VIDEO_RAM_END: u16 = 0x9FFF;
 
+/// RAM in the cartridge, if any.
+const EXTERNAL_RAM_START: u16 = 0xA000;
+const EXTERNAL_RAM_END: u16 = 0xBFFF;
+
+/// Work RAM. Its second half is switchable on CGB.
 const WORK_RAM_START: u16 = 0xC000;
-const WORK_RAM_END: u16 = 0xCFFF;
+const WORK_RAM_END: u16 = 0xDFFF;
 
-const ECHO_RAM_START: u16 = 0xD000;
-const ECHO_RAM_END: u16 = 0xDFFF;
+/// A mirror of most of work RAM, due to how the address lines are wired.
+const ECHO_RAM_START: u16 = 0xE000;
+const ECHO_RAM_END: u16 = 0xFDFF;
 
 const OAM_RAM_START: u16 = 0xFE00;
 const OAM_RAM_END: u16 = 0xFE9F;
 
+/// A region Nintendo forbids to use, that is not connected to any memory.
+const UNUSABLE_START: u16 = 0xFEA0;
+const UNUSABLE_END: u16 = 0xFEFF;
+
 const MAPPED_KEYPAD_START: u16 = 0xFF00;
 const MAPPED_KEYPAD_END: u16 = 0xFF00;
 
+const MAPPED_SERIAL_START: u16 = 0xFF01;
+const MAPPED_SERIAL_END: u16 = 0xFF02;
+
 const MAPPED_TIMER_START: u16 = 0xFF04;
 const MAPPED_TIMER_END: u16 = 0xFF07;
 
+const MAPPED_INTERRUPT_FLAG: u16 = 0xFF0F;
+
 const APU_REGISTERS_START: u16 = 0xFF10;
 const APU_REGISTERS_END: u16 = 0xFF3F;
 
+/// LCD registers, except DMA which sits in their middle.
+const LCD_REGISTERS_START: u16 = 0xFF40;
+const LCD_REGISTERS_END: u16 = 0xFF4B;
+
 const MAPPED_DMA: u16 = 0xFF46;
 
+/// Writing a non-zero value to this register unmaps the boot ROM.
+const MAPPED_BOOTROM_UNMAP: u16 = 0xFF50;
+
+/// Selects the bank of work RAM mapped at 0xD000-0xDFFF, on CGB only.
+const MAPPED_SVBK: u16 = 0xFF70;
+
+const HIGH_RAM_START: u16 = 0xFF80;
+const HIGH_RAM_END: u16 = 0xFFFE;
+
 const INTERRUPTS_START: u16 = 0xFFFF;
 const INTERRUPTS_END: u16 = 0xFFFF;
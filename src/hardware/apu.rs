@@ -28,6 +28,10 @@ const TICKS_IN_SAMPLE_RATE: u32 = hardware::MASTER_CLOCK / SAMPLE_RATE;
 
 pub struct Apu {
     volume: MasterVolume,
+    /// Volume of the left and right outputs. It corresponds to register NR50.
+    master_volume: u8,
+    /// Which channels are sent to the left and right outputs. It corresponds to register NR51.
+    panning: u8,
     ch1: SquareChannel,
     ch2: SquareChannel,
     ch3: WaveChannel,
@@ -43,6 +47,8 @@ impl Apu {
     pub fn new(buffer: mpsc::SyncSender<(u8, u8)>) -> Self {
         Self {
             volume: Default::default(),
+            master_volume: 0,
+            panning: 0,
             ch1: Default::default(),
             ch2: Default::default(),
             ch3: Default::default(),
@@ -92,7 +98,6 @@ impl Apu {
                 (self.ch2.length.enabled as u8) << 6 | ((self.ch2.raw_period & 0x0700) >> 8) as u8
             }
             0xA => (self.ch3.enabled as u8) << 7,
-            0xB => unreachable!(),
             0xC => u8::from(self.ch3.volume) << 5,
             0xD => self.ch3.raw_period as u8,
             0xE => {
@@ -105,7 +110,13 @@ impl Apu {
                     | self.ch4.clock_divider
             }
             0x13 => (self.ch4.length.enabled as u8) << 6,
+            0x14 => self.master_volume,
+            0x15 => self.panning,
+            // Bits 4-6 are unused and always read as 1.
+            0x16 => self.volume.0.get_range(0..=7) | 0b01110000,
             0x20..=0x2F => self.ch3.wave_ram[idx - 0x20],
+            // Write-only and unused registers read as 0xFF.
+            0x5 | 0xB | 0xF | 0x10 | 0x17..=0x1F => 0xFF,
             _ => unreachable!(),
         }
     }
@@ -163,7 +174,12 @@ impl Apu {
                     self.ch4.trigger();
                 }
             }
+            0x14 => self.master_volume = val,
+            0x15 => self.panning = val,
+            // Only the audio on/off bit is writable.
+            0x16 => self.volume.0.set_range(7..=7, val >> 7),
             0x20..=0x2F => self.ch3.wave_ram[idx - 0x20] = val,
+            0x5 | 0xF | 0x17..=0x1F => (),
             _ => unreachable!(),
         }
     }
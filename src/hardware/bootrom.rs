@@ -0,0 +1,153 @@
+//! The `bootrom` module identifies boot ROMs, and with them the hardware model being emulated.
+//! Every model has its own boot ROM, which leaves the system in a slightly different state.
+//!
+//! See <https://gbdev.io/pandocs/Power_Up_Sequence.html>
+
+use std::{fs, io, path::Path, str::FromStr};
+
+use sha1::{Digest, Sha1};
+
+/// Size, in bytes, of the boot ROM of all supported models.
+const SIZE: usize = 256;
+
+/// A model of the Game Boy family, as far as the boot process is concerned.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Model {
+    /// The very first DMG units, with an early boot ROM.
+    Dmg0,
+    #[default]
+    Dmg,
+    /// The Game Boy Pocket and Light.
+    Mgb,
+    /// The Super Game Boy.
+    Sgb,
+    /// The Super Game Boy 2.
+    Sgb2,
+}
+
+impl Model {
+    const ALL: [Self; 5] = [Self::Dmg0, Self::Dmg, Self::Mgb, Self::Sgb, Self::Sgb2];
+
+    /// Returns the SHA-1 hash of the boot ROM of this model.
+    const fn boot_rom_hash(self) -> &'static str {
+        match self {
+            Self::Dmg0 => "8bd501e31921e9601788316dbd3ce9833a97bcbc",
+            Self::Dmg => "4ed31ec6b0b175bb109c0eb5fd3d193da823339f",
+            Self::Mgb => "4e68f9da03c310e84c523654b9026e51f26ce7f0",
+            Self::Sgb => "aa2f50a77dfb4823da96ba99309085a3c6278515",
+            Self::Sgb2 => "93407ea10d2f30ab96a314d8eca44fe160aea734",
+        }
+    }
+
+    /// Returns the value of the master counter of the timer when the boot ROM ends.
+    /// Only its upper byte, register DIV, is documented.
+    pub const fn divider_after_boot(self) -> u16 {
+        match self {
+            Self::Dmg0 => 0x1800,
+            Self::Dmg | Self::Mgb => 0xABCC,
+            // It depends on how long the SNES takes to talk to the SGB.
+            Self::Sgb | Self::Sgb2 => 0x0000,
+        }
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dmg0" => Ok(Self::Dmg0),
+            "dmg" => Ok(Self::Dmg),
+            "mgb" => Ok(Self::Mgb),
+            "sgb" => Ok(Self::Sgb),
+            "sgb2" => Ok(Self::Sgb2),
+            _ => Err(format!("unknown model: {s}")),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BootRomError {
+    #[error("failed to read the boot ROM: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("boot ROMs are {SIZE} bytes, but this one is {0}")]
+    Size(usize),
+
+    #[error("unknown boot ROM with SHA-1 {0}")]
+    Unknown(String),
+}
+
+/// A boot ROM known to belong to a certain model.
+pub struct BootRom {
+    data: Box<[u8; SIZE]>,
+    model: Model,
+}
+
+impl BootRom {
+    /// Reads a boot ROM from a file. See [`Self::from_bytes`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BootRomError> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Identifies the model a boot ROM belongs to.
+    /// Only dumps of official boot ROMs are accepted.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, BootRomError> {
+        let data = check_size(data)?;
+        // SHA-1 is not secure, but it is what boot ROM dumps are commonly identified by.
+        let hash = format!("{:x}", Sha1::digest(&data[..]));
+        let model = Model::ALL
+            .into_iter()
+            .find(|model| model.boot_rom_hash() == hash)
+            .ok_or(BootRomError::Unknown(hash))?;
+        Ok(Self { data, model })
+    }
+
+    /// Wraps a boot ROM that is trusted to belong to `model`, such as a homebrew one.
+    /// Only its size is checked.
+    pub fn with_model(data: Vec<u8>, model: Model) -> Result<Self, BootRomError> {
+        Ok(Self {
+            data: check_size(data)?,
+            model,
+        })
+    }
+
+    /// Wraps a boot ROM embedded in the emulator.
+    pub(crate) fn embedded(data: &[u8; SIZE], model: Model) -> Self {
+        Self {
+            data: Box::new(*data),
+            model,
+        }
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        self.data[addr as usize]
+    }
+}
+
+fn check_size(data: Vec<u8>) -> Result<Box<[u8; SIZE]>, BootRomError> {
+    data.into_boxed_slice()
+        .try_into()
+        .map_err(|data: Box<[u8]>| BootRomError::Size(data.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_bad_boot_roms() {
+        assert!(matches!(
+            BootRom::from_bytes(vec![0; 255]),
+            Err(BootRomError::Size(255))
+        ));
+        assert!(matches!(
+            BootRom::from_bytes(vec![0; 256]),
+            Err(BootRomError::Unknown(_))
+        ));
+    }
+}
@@ -0,0 +1,182 @@
+//! The `bus` module defines how the CPU reaches memory and peripherals.
+//! The CPU only ever talks to a [`Bus`], so it can run against the real hardware,
+//! against a flat memory in tests, or against a wrapper that records every access.
+
+use crate::hardware::{interrupts::Interrupt, Hardware, M_CYCLE};
+
+/// The bus the CPU accesses memory and peripherals through.
+/// Every access takes one M-cycle, during which the rest of the system keeps running.
+pub trait Bus {
+    /// Reads a byte, advancing the rest of the system by one M-cycle.
+    fn read(&mut self, addr: u16) -> u8;
+    /// Writes a byte, advancing the rest of the system by one M-cycle.
+    fn write(&mut self, addr: u16, val: u8);
+    /// Advances the rest of the system by one M-cycle, without accessing memory.
+    fn tick(&mut self);
+    /// Reads a byte without advancing time nor causing side effects.
+    fn peek(&self, addr: u16) -> u8;
+
+    /// Returns the highest-priority interrupt that is both requested and enabled.
+    fn pending_interrupt(&self) -> Option<Interrupt>;
+    /// Clears the request of an interrupt the CPU started servicing.
+    fn acknowledge_interrupt(&mut self, int: Interrupt);
+    /// Returns whether a button is pressed, which wakes the CPU from STOP.
+    fn joypad_pressed(&self) -> bool;
+    /// Resets the divider register, as STOP does.
+    fn reset_divider(&mut self);
+}
+
+impl Bus for Hardware {
+    // Inherent methods of Hardware are called explicitly, as they share names with this trait.
+
+    fn read(&mut self, addr: u16) -> u8 {
+        Bus::tick(self);
+        Hardware::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        Bus::tick(self);
+        Hardware::write(self, addr, val)
+    }
+
+    fn tick(&mut self) {
+        Hardware::tick(self, M_CYCLE);
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        Hardware::read(self, addr)
+    }
+
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        self.interrupts.pending()
+    }
+
+    fn acknowledge_interrupt(&mut self, int: Interrupt) {
+        self.interrupts.acknowledge(int);
+    }
+
+    fn joypad_pressed(&self) -> bool {
+        self.keypad.any_pressed()
+    }
+
+    fn reset_divider(&mut self) {
+        self.timer.reset_divider();
+    }
+}
+
+impl<B: Bus + ?Sized> Bus for &mut B {
+    fn read(&mut self, addr: u16) -> u8 {
+        (**self).read(addr)
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        (**self).write(addr, val);
+    }
+
+    fn tick(&mut self) {
+        (**self).tick();
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        (**self).peek(addr)
+    }
+
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        (**self).pending_interrupt()
+    }
+
+    fn acknowledge_interrupt(&mut self, int: Interrupt) {
+        (**self).acknowledge_interrupt(int);
+    }
+
+    fn joypad_pressed(&self) -> bool {
+        (**self).joypad_pressed()
+    }
+
+    fn reset_divider(&mut self) {
+        (**self).reset_divider();
+    }
+}
+
+/// What the CPU did with the bus during one M-cycle.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+    Read(u16, u8),
+    Write(u16, u8),
+    Idle,
+}
+
+/// Wraps another bus, recording every access made through it.
+pub struct Recorder<B> {
+    inner: B,
+    accesses: Vec<Access>,
+}
+
+impl<B: Bus> Recorder<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            accesses: Vec::new(),
+        }
+    }
+
+    /// Returns the accesses recorded so far, one per M-cycle, oldest first.
+    pub fn accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    /// Returns the accesses recorded so far, and forgets them.
+    pub fn take_accesses(&mut self) -> Vec<Access> {
+        std::mem::take(&mut self.accesses)
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bus> Bus for Recorder<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let val = self.inner.read(addr);
+        self.accesses.push(Access::Read(addr, val));
+        val
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.inner.write(addr, val);
+        self.accesses.push(Access::Write(addr, val));
+    }
+
+    fn tick(&mut self) {
+        self.inner.tick();
+        self.accesses.push(Access::Idle);
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.inner.peek(addr)
+    }
+
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        self.inner.pending_interrupt()
+    }
+
+    fn acknowledge_interrupt(&mut self, int: Interrupt) {
+        self.inner.acknowledge_interrupt(int);
+    }
+
+    fn joypad_pressed(&self) -> bool {
+        self.inner.joypad_pressed()
+    }
+
+    fn reset_divider(&mut self) {
+        self.inner.reset_divider();
+    }
+}
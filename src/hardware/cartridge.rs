@@ -1,9 +1,19 @@
+mod eeprom;
+mod flash;
 mod header;
+mod infrared;
 mod mbc;
+mod rtc;
+mod save;
+mod sensor;
 
-use std::io::{self, Read, Seek, SeekFrom};
+use std::io::{self, Read};
 
+pub use infrared::Infrared;
 use mbc::Mbc;
+use rtc::Rtc;
+pub use save::{FileStorage, SaveStorage};
+pub use sensor::{Frame, ImageSource};
 
 pub struct Cartridge {
     hw: Hardware,
@@ -13,42 +23,164 @@ pub struct Cartridge {
     /// having a battery means having to store the cartridge state
     /// in a file.
     has_battery: bool,
+    /// Whether the cartridge has a real-time clock, which is saved along with RAM.
+    has_rtc: bool,
+    /// Where the state is saved, if the cartridge has a battery.
+    storage: Option<Box<dyn SaveStorage>>,
+    /// Whether RAM was disabled after being written, which games do when they are done saving.
+    save_requested: bool,
 
     mbc: Mbc,
 }
 
 impl Cartridge {
     /// Builds a cartridge hardware emulator according to a header contained in the cartridge itself.
-    pub fn new_from_header(mut data: Box<dyn RomSource>) -> io::Result<Self> {
-        let cartridge_type = header::cartridge_type(&mut data)?;
-        let rom_banks = header::rom_banks(&mut data)?;
-        let ram_banks = header::ram_banks(&mut data, cartridge_type)?;
+    /// The whole ROM is read into memory, so `source` is not needed afterwards.
+    pub fn new_from_header(mut source: impl Read) -> io::Result<Self> {
+        let mut data = Vec::new();
+        source.read_to_end(&mut data)?;
+        Self::from_rom(data)
+    }
+
+    /// Builds a cartridge hardware emulator around a ROM already in memory.
+    /// It fails if the ROM is too short to contain a header.
+    pub fn from_rom(data: Vec<u8>) -> io::Result<Self> {
+        let cartridge_type = header::cartridge_type(&data)?;
+        let rom_banks = header::rom_banks(&data)?;
+        let mut ram = Ram::new(match cartridge_type.builtin_ram() {
+            Some(len) => len,
+            None => header::ram_size(&data)?,
+        });
+        if let Some(len) = cartridge_type.flash() {
+            // Erased flash reads 0xFF.
+            ram.data.resize(ram.data.len() + len, 0xFF);
+        }
+        let mbc = cartridge_type.mbc(&data)?;
         Ok(Self {
-            hw: Hardware::new(data, rom_banks, ram_banks),
+            hw: Hardware::new(data, rom_banks, ram),
             has_battery: cartridge_type.has_battery(),
-            mbc: cartridge_type.mbc(),
+            has_rtc: cartridge_type.has_rtc(),
+            storage: None,
+            save_requested: false,
+            mbc,
         })
     }
-}
 
-pub trait RomSource: Read + Seek {}
-impl<T: Read + Seek> RomSource for T {}
+    /// Sets where the state of the cartridge is saved, and restores the last save, if any.
+    /// Cartridges without a battery lose their state when turned off, so they ignore `storage`.
+    pub fn set_storage(&mut self, mut storage: Box<dyn SaveStorage>) -> io::Result<()> {
+        if !self.has_battery {
+            return Ok(());
+        }
+        if let Some(save) = storage.load()? {
+            let (ram, footer) = save.split_at(save.len().min(self.hw.ram.data.len()));
+            self.hw.ram.restore(ram);
+            if self.has_rtc {
+                self.hw.rtc.restore(footer);
+            } else {
+                self.mbc.restore(footer);
+            }
+        }
+        self.storage = Some(storage);
+        Ok(())
+    }
+
+    /// Makes the real-time clock, if any, keep counting while the emulator is not running,
+    /// by adding the time the host spent between a save and its load.
+    /// It is meant to be called before [`Self::set_storage`].
+    pub fn set_rtc_follows_host(&mut self, follow: bool) {
+        self.hw.rtc.follow_host = follow;
+    }
+
+    /// Saves the state of the cartridge, if it has a battery and changed since the last save.
+    /// A real-time clock always changes, and is saved after RAM in a 48-byte footer.
+    /// So does the clock of HuC3, saved with the memory of its microcontroller.
+    pub fn save(&mut self) -> io::Result<()> {
+        self.save_requested = false;
+        let Some(storage) = self.storage.as_mut() else {
+            return Ok(());
+        };
+        let footer = if self.has_rtc {
+            Some(self.hw.rtc.footer().to_vec())
+        } else {
+            self.mbc.footer()
+        };
+        if let Some(footer) = footer {
+            let mut save = self.hw.ram.data.clone();
+            save.extend_from_slice(&footer);
+            storage.store(&save)?;
+        } else if self.hw.ram.dirty {
+            storage.store(&self.hw.ram.data)?;
+        }
+        self.hw.ram.dirty = false;
+        Ok(())
+    }
+
+    /// Returns whether the game is done writing RAM, so it should be saved.
+    pub fn save_requested(&self) -> bool {
+        self.save_requested
+    }
+
+    /// Returns the state of the rumble motor, if it was turned on or off since the last call.
+    pub fn take_rumble(&mut self) -> Option<bool> {
+        std::mem::take(&mut self.hw.motor_changed).then_some(self.hw.motor)
+    }
+
+    /// Connects the infrared port, if any, to a peer.
+    pub fn set_infrared(&mut self, port: Box<dyn Infrared>) {
+        self.hw.infrared = Some(port);
+    }
+
+    /// Gives the camera, if any, something to take pictures of.
+    pub fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.hw.image_source = Some(source);
+    }
+
+    /// Tilts the accelerometer, if any, along the horizontal (`x`) and vertical (`y`) axes
+    /// of the screen, in g: 1.0 means tilting right or down by 90°.
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.hw.tilt = (x, y);
+    }
+
+    /// Advances the real-time clock, if any, by a number of clock ticks.
+    pub fn tick(&mut self, ticks: u8) {
+        if self.has_rtc {
+            self.hw.rtc.tick(ticks);
+        }
+        self.mbc.tick(&mut self.hw, ticks);
+    }
+
+    /// Reads from the cartridge ROM (0x0000–0x7FFF) or external RAM (0xA000–0xBFFF).
+    pub fn read(&self, addr: u16) -> u8 {
+        self.mbc.read(&self.hw, addr)
+    }
+
+    /// Writes to the cartridge ROM (0x0000–0x7FFF), where it usually
+    /// configures the memory controller, or to external RAM (0xA000–0xBFFF).
+    pub fn write(&mut self, addr: u16, val: u8) {
+        let ram_enabled = self.hw.ram.enabled;
+        self.mbc.write(&mut self.hw, addr, val);
+        if ram_enabled && !self.hw.ram.enabled && self.hw.ram.dirty {
+            self.save_requested = true;
+        }
+    }
+}
 
 struct Rom {
-    data: Box<dyn RomSource>,
-    /// Number of banks composing the ROM.
-    banks: u8,
+    data: Vec<u8>,
+    /// Number of banks composing the ROM, up to 512.
+    banks: u16,
     /// The currently selected ROM bank.
     /// The default value is 1, since the first
     /// [`Self::BANK_SIZE`] bytes are directly accessible.
-    curr_bank: u8,
+    curr_bank: u16,
 }
 
 impl Rom {
     /// Size, in bytes, of each ROM bank.
     const BANK_SIZE: u16 = 16 * 1024;
 
-    fn new(data: Box<dyn RomSource>, banks: u8) -> Self {
+    fn new(data: Vec<u8>, banks: u16) -> Self {
         Self {
             data,
             banks,
@@ -57,28 +189,36 @@ impl Rom {
     }
 
     /// Reads data at an absolute address.
-    fn at(&mut self, addr: u16) -> io::Result<u8> {
-        read_at(&mut self.data, addr)
+    /// Past the end of a ROM shorter than its header claims, the bus is left floating and reads 0xFF.
+    fn at(&self, addr: u32) -> u8 {
+        self.data.get(addr as usize).copied().unwrap_or(0xFF)
     }
 
     /// Reads data relative to the currently selected bank.
-    fn at_current_bank(&mut self, addr: u16) -> io::Result<u8> {
-        self.at(self.curr_bank as u16 * Self::BANK_SIZE + addr)
+    fn at_current_bank(&self, addr: u16) -> u8 {
+        self.at_bank(self.curr_bank, addr)
+    }
+
+    /// Reads data relative to a bank, masked like [`Self::set_bank_unchecked`] does.
+    fn at_bank(&self, bank: u16, addr: u16) -> u8 {
+        let bank = bank & self.bank_mask();
+        self.at(bank as u32 * Self::BANK_SIZE as u32 + addr as u32)
     }
 
     fn set_bank(&mut self, bank: u8) {
-        let mut bank = if bank == 0 { 1 } else { bank };
-        // If bank number is too high, it is masked by the amount of bits
-        // required to represent the bank count.
-        bank &= (1 << self.banks.ilog2() as u8) - 1;
+        let bank = if bank == 0 { 1 } else { bank };
+        self.set_bank_unchecked(bank.into());
+    }
 
-        self.curr_bank = bank;
+    /// Selects a bank, bank 0 included, as controllers that do not translate it to 1 do.
+    fn set_bank_unchecked(&mut self, bank: u16) {
+        self.curr_bank = bank & self.bank_mask();
     }
 
-    /// Sets the upper two bits of the current bank selection.
-    fn set_bank_extended(&mut self, ext: u8) {
-        let ext = ext & 0b00000011;
-        self.curr_bank = (self.curr_bank & 0b0011111) + (ext << 5);
+    /// If bank number is too high, it is masked by the amount of bits
+    /// required to represent the bank count.
+    fn bank_mask(&self) -> u16 {
+        (1 << self.banks.ilog2()) - 1
     }
 }
 
@@ -87,50 +227,74 @@ struct Ram {
     banks: u8,
     curr_bank: u8,
     enabled: bool,
+    /// Whether data changed since it was last saved.
+    dirty: bool,
 }
 
 impl Ram {
     /// Size, in bytes, of each RAM bank.
     const BANK_SIZE: u16 = 8 * 1024;
 
-    fn new(banks: u8) -> Self {
+    /// Creates RAM of `len` bytes. RAM smaller than a bank, such as 2 KiB chips
+    /// or RAM built into the controller, takes a single bank.
+    fn new(len: usize) -> Self {
         Self {
-            data: vec![0; banks as usize * Self::BANK_SIZE as usize],
-            banks,
+            data: vec![0; len],
+            banks: len.div_ceil(Self::BANK_SIZE as usize) as u8,
             curr_bank: 0,
             enabled: false,
+            dirty: false,
         }
     }
 
+    /// Restores data from a save. Saves of a different size, as some emulators make,
+    /// are truncated or padded.
+    fn restore(&mut self, save: &[u8]) {
+        let len = save.len().min(self.data.len());
+        self.data[..len].copy_from_slice(&save[..len]);
+    }
+
     /// Reads data at an absolute address.
-    fn read(&self, addr: u16) -> u8 {
+    /// Disabled or missing RAM leaves the bus floating, so it reads 0xFF.
+    fn read(&self, addr: u32) -> u8 {
         if !self.enabled {
             return 0xFF;
         }
-        self.data[addr as usize]
+        self.data.get(addr as usize).copied().unwrap_or(0xFF)
     }
 
     /// Reads data relative to the currently selected bank.
-    fn read_current_bank(&mut self, addr: u16) -> u8 {
-        self.read(self.curr_bank as u16 * Self::BANK_SIZE + addr)
+    fn read_current_bank(&self, addr: u16) -> u8 {
+        self.read(self.curr_bank as u32 * Self::BANK_SIZE as u32 + addr as u32)
     }
 
     /// Writes data at an absolute address.
-    fn write(&mut self, addr: u16, val: u8) {
+    fn write(&mut self, addr: u32, val: u8) {
         if !self.enabled {
             return;
         }
-        self.data[addr as usize] = val;
+        if let Some(byte) = self.data.get_mut(addr as usize) {
+            *byte = val;
+            self.dirty = true;
+        }
     }
 
     /// Writes data relative to the currently selected bank.
     fn write_current_bank(&mut self, addr: u16, val: u8) {
-        self.write(self.curr_bank as u16 * Self::BANK_SIZE + addr, val)
+        self.write(
+            self.curr_bank as u32 * Self::BANK_SIZE as u32 + addr as u32,
+            val,
+        )
     }
 
     fn set_current_bank(&mut self, bank: u8) {
         self.curr_bank = bank & 0b00000011;
     }
+
+    /// Selects one of up to 16 banks, as MBC5 does.
+    fn set_current_bank_wide(&mut self, bank: u8) {
+        self.curr_bank = bank & 0b00001111;
+    }
 }
 
 struct Hardware {
@@ -138,15 +302,30 @@ struct Hardware {
     pub ram: Ram,
     pub rtc: Rtc,
     pub banking_mode: BankingMode,
+    /// Whether the rumble motor, if any, is on.
+    pub motor: bool,
+    /// Whether the rumble motor was turned on or off since the last time it was observed.
+    pub motor_changed: bool,
+    /// The peer of the infrared port, if any is connected.
+    pub infrared: Option<Box<dyn Infrared>>,
+    /// What the camera, if any, sees.
+    pub image_source: Option<Box<dyn ImageSource>>,
+    /// How much the accelerometer, if any, is tilted. See [`Cartridge::set_tilt`].
+    pub tilt: (f32, f32),
 }
 
 impl Hardware {
-    fn new(data: Box<dyn RomSource>, rom_banks: u8, ram_banks: u8) -> Self {
+    fn new(data: Vec<u8>, rom_banks: u16, ram: Ram) -> Self {
         Self {
             rom: Rom::new(data, rom_banks),
-            ram: Ram::new(ram_banks),
+            ram,
             rtc: Rtc::new(),
             banking_mode: BankingMode::Rom,
+            motor: false,
+            motor_changed: false,
+            infrared: None,
+            image_source: None,
+            tilt: (0.0, 0.0),
         }
     }
 }
@@ -163,118 +342,16 @@ enum BankingMode {
     Rtc,
 }
 
-struct Rtc {
-    registers: RtcRegisters,
-    curr_register: u8,
-    latched: bool,
-    enabled: bool,
-}
-
-impl Rtc {
-    fn new() -> Self {
-        Self {
-            registers: Default::default(),
-            curr_register: 0,
-            latched: false,
-            enabled: true,
-        }
-    }
-
-    pub fn read_register(&self, idx: usize) -> u8 {
-        match idx {
-            0 => self.registers.seconds,
-            1 => self.registers.minutes,
-            2 => self.registers.hours,
-            3 => self.registers.days_lower,
-            4 => self.registers.bools.0.into_value(),
-            _ => unreachable!(),
-        }
-    }
-
-    pub fn write_register(&mut self, idx: usize, val: u8) {
-        match idx {
-            0 => self.registers.seconds = val,
-            1 => self.registers.minutes = val,
-            2 => self.registers.hours = val,
-            3 => self.registers.days_lower = val,
-            4 => self.registers.bools = RtcBoolRegisters(bitmaps::Bitmap::<8>::from_value(val)),
-            _ => unreachable!(),
-        }
-    }
-
-    fn read_current_register(&self) -> u8 {
-        if !self.enabled {
-            return 0xFF;
-        }
-        self.read_register(self.curr_register.into())
-    }
-
-    fn write_current_register(&mut self, val: u8) {
-        if !self.enabled {
-            return;
-        }
-        self.write_register(self.curr_register.into(), val)
-    }
-
-    fn set_current_register(&mut self, reg: u8) {
-        self.curr_register = reg;
+/// Builds a ROM with the given cartridge type, and ROM and RAM size codes,
+/// whose banks of `bank_size` bytes start with their own number, in little endian.
+#[cfg(test)]
+pub(crate) fn test_rom(kind: u8, rom_code: u8, ram_code: u8, bank_size: usize) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000 << rom_code];
+    for (bank, data) in rom.chunks_exact_mut(bank_size).enumerate() {
+        data[..2].copy_from_slice(&(bank as u16).to_le_bytes());
     }
-
-    /// Sets whether the RTC value is latched to a certain register.
-    /// The RTC value is only latched when it is first "unlatched",
-    /// i.e. it is required to call [`Self::set_latched`] passing
-    /// `false` and then again passing `true`.
-    fn set_latched(&mut self, latched: bool) {
-        if !self.latched && latched {
-            todo!("Save latched value");
-        }
-        self.latched = latched;
-    }
-}
-
-#[derive(Default)]
-struct RtcRegisters {
-    seconds: u8,
-    minutes: u8,
-    hours: u8,
-    /// Lower bits of the day counter.
-    /// The most significant bit is contained in [`Self::bools`].
-    days_lower: u8,
-    bools: RtcBoolRegisters,
-}
-
-#[derive(Default)]
-struct RtcBoolRegisters(bitmaps::Bitmap<8>);
-
-impl RtcBoolRegisters {
-    fn days_upper(&self) -> bool {
-        self.0.get(0)
-    }
-
-    fn enabled(&self) -> bool {
-        self.0.get(6)
-    }
-
-    fn days_overflowed(&self) -> bool {
-        self.0.get(7)
-    }
-
-    fn set_days_upper(&mut self, val: bool) {
-        self.0.set(0, val);
-    }
-
-    fn set_enabled(&mut self, val: bool) {
-        self.0.set(6, val);
-    }
-
-    fn set_days_overflowed(&mut self, val: bool) {
-        self.0.set(7, val);
-    }
-}
-
-fn read_at<R: Read + Seek>(data: &mut R, addr: u16) -> io::Result<u8> {
-    data.seek(SeekFrom::Start(addr as u64))?;
-    let mut buf = [0; 1];
-    data.read_exact(&mut buf)?;
-    Ok(buf[0])
+    rom[0x147] = kind;
+    rom[0x148] = rom_code;
+    rom[0x149] = ram_code;
+    rom
 }
@@ -0,0 +1,241 @@
+//! The `eeprom` module emulates the 93LC56 serial EEPROM of MBC7 cartridges.
+//!
+//! The game drives the chip select, clock and data lines by hand, one bit at a time:
+//! a start bit, a 2-bit opcode and an 8-bit address are shifted in, then a 16-bit word
+//! is shifted in or out. Its 128 words are kept in cartridge RAM, so they are saved like it.
+//!
+//! See <https://gbdev.io/pandocs/MBC7.html#eeprom>
+
+use crate::hardware::cartridge::Ram;
+
+/// Size, in bytes, of the EEPROM.
+pub const SIZE: usize = 256;
+
+pub struct Eeprom {
+    /// Chip select, clock and data in, as last written.
+    select: bool,
+    clock: bool,
+    data_in: bool,
+    /// What the chip drives on its data out line. It is high when ready for a command.
+    data_out: bool,
+    state: State,
+    /// Whether erasing and writing are allowed. The chip powers up protected.
+    write_enabled: bool,
+}
+
+enum State {
+    /// Waiting for a start bit.
+    Idle,
+    /// Shifting in the opcode and the address.
+    Command { bits: u16, count: u8 },
+    /// Shifting out words, from an address on, most significant bit first.
+    Reading { addr: u8, count: u8 },
+    /// Shifting in a word to write at an address, or everywhere if `None`.
+    Writing {
+        addr: Option<u8>,
+        bits: u16,
+        count: u8,
+    },
+}
+
+impl Eeprom {
+    pub fn new() -> Self {
+        Self {
+            select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+            state: State::Idle,
+            write_enabled: false,
+        }
+    }
+
+    /// Reads the lines: chip select in bit 7, clock in bit 6, data in in bit 1
+    /// and data out in bit 0.
+    pub fn read(&self) -> u8 {
+        (self.select as u8) << 7
+            | (self.clock as u8) << 6
+            | (self.data_in as u8) << 1
+            | self.data_out as u8
+    }
+
+    /// Drives the lines, laid out as in [`Self::read`]. The chip samples data in
+    /// on rising edges of the clock, while selected.
+    pub fn write(&mut self, ram: &mut Ram, val: u8) {
+        let select = val & 0b10000000 != 0;
+        let clock = val & 0b01000000 != 0;
+        self.data_in = val & 0b00000010 != 0;
+        if !select {
+            // Deselecting aborts any command.
+            self.state = State::Idle;
+            self.data_out = true;
+        } else if clock && !self.clock {
+            self.shift(ram);
+        }
+        self.select = select;
+        self.clock = clock;
+    }
+
+    /// Handles a rising edge of the clock.
+    fn shift(&mut self, ram: &mut Ram) {
+        let bit = self.data_in as u16;
+        match &mut self.state {
+            State::Idle => {
+                if self.data_in {
+                    self.state = State::Command { bits: 0, count: 0 };
+                }
+            }
+            State::Command { bits, count } => {
+                *bits = *bits << 1 | bit;
+                *count += 1;
+                if *count == 10 {
+                    let bits = *bits;
+                    self.execute(ram, (bits >> 8) as u8, bits as u8);
+                }
+            }
+            State::Reading { addr, count } => {
+                // Reading goes on with the next word, until deselected.
+                if *count == 16 {
+                    *addr = (*addr + 1) & 0x7F;
+                    *count = 0;
+                }
+                let word = read_word(ram, *addr);
+                self.data_out = word >> (15 - *count) & 1 != 0;
+                *count += 1;
+            }
+            State::Writing { addr, bits, count } => {
+                *bits = *bits << 1 | bit;
+                *count += 1;
+                if *count == 16 {
+                    let (addr, word) = (*addr, *bits);
+                    match addr {
+                        Some(addr) => self.program(ram, addr, word),
+                        None => (0..0x80).for_each(|addr| self.program(ram, addr, word)),
+                    }
+                    self.state = State::Idle;
+                }
+            }
+        }
+    }
+
+    /// Executes a command, once its opcode and address are shifted in.
+    fn execute(&mut self, ram: &mut Ram, opcode: u8, addr: u8) {
+        // Words are 16 bits wide, so the top address bit is ignored.
+        let word_addr = addr & 0x7F;
+        self.state = State::Idle;
+        match opcode {
+            // A dummy 0 comes before the word.
+            0b10 => {
+                self.data_out = false;
+                self.state = State::Reading {
+                    addr: word_addr,
+                    count: 0,
+                };
+            }
+            0b01 => {
+                self.state = State::Writing {
+                    addr: Some(word_addr),
+                    bits: 0,
+                    count: 0,
+                }
+            }
+            0b11 => self.program(ram, word_addr, 0xFFFF),
+            // The other commands are told apart by the top address bits.
+            0b00 => match addr >> 6 {
+                0b11 => self.write_enabled = true,
+                0b00 => self.write_enabled = false,
+                0b10 => (0..0x80).for_each(|addr| self.program(ram, addr, 0xFFFF)),
+                0b01 => {
+                    self.state = State::Writing {
+                        addr: None,
+                        bits: 0,
+                        count: 0,
+                    }
+                }
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Writes a word, if allowed. Programming completes instantly, so the chip is always ready.
+    fn program(&mut self, ram: &mut Ram, addr: u8, word: u16) {
+        if self.write_enabled {
+            let [low, high] = word.to_le_bytes();
+            ram.write(addr as u32 * 2, low);
+            ram.write(addr as u32 * 2 + 1, high);
+        }
+        self.data_out = true;
+    }
+}
+
+/// Reads a word, stored in little endian.
+fn read_word(ram: &Ram, addr: u8) -> u16 {
+    u16::from_le_bytes([ram.read(addr as u32 * 2), ram.read(addr as u32 * 2 + 1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ram() -> Ram {
+        let mut ram = Ram::new(SIZE);
+        ram.enabled = true;
+        ram
+    }
+
+    /// Shifts bits in, most significant first, and returns what was shifted out.
+    fn shift(eeprom: &mut Eeprom, ram: &mut Ram, bits: u32, count: u8) -> u32 {
+        (0..count).rev().fold(0, |out, idx| {
+            let data_in = (bits >> idx & 1) as u8;
+            eeprom.write(ram, 0x80 | data_in << 1);
+            eeprom.write(ram, 0xC0 | data_in << 1);
+            out << 1 | (eeprom.read() & 1) as u32
+        })
+    }
+
+    /// Sends a command, preceded by its start bit.
+    fn command(eeprom: &mut Eeprom, ram: &mut Ram, opcode: u32, addr: u32) {
+        eeprom.write(ram, 0x00);
+        shift(eeprom, ram, 1 << 10 | opcode << 8 | addr, 11);
+    }
+
+    #[test]
+    fn write_then_read() {
+        let (mut eeprom, mut ram) = (Eeprom::new(), ram());
+
+        // Writing is ignored until enabled.
+        command(&mut eeprom, &mut ram, 0b01, 0x05);
+        shift(&mut eeprom, &mut ram, 0x1234, 16);
+        assert_eq!(read_word(&ram, 0x05), 0x0000);
+
+        command(&mut eeprom, &mut ram, 0b00, 0xC0);
+        command(&mut eeprom, &mut ram, 0b01, 0x05);
+        shift(&mut eeprom, &mut ram, 0x1234, 16);
+        assert_eq!(eeprom.read() & 1, 1);
+        assert_eq!(ram.data[0x0A..0x0C], [0x34, 0x12]);
+        assert!(ram.dirty);
+
+        // The dummy bit is already out, then words follow one another.
+        command(&mut eeprom, &mut ram, 0b10, 0x85);
+        assert_eq!(eeprom.read() & 1, 0);
+        assert_eq!(shift(&mut eeprom, &mut ram, 0, 16), 0x1234);
+        assert_eq!(shift(&mut eeprom, &mut ram, 0, 16), 0x0000);
+    }
+
+    #[test]
+    fn erase() {
+        let (mut eeprom, mut ram) = (Eeprom::new(), ram());
+        command(&mut eeprom, &mut ram, 0b00, 0xC0);
+        command(&mut eeprom, &mut ram, 0b11, 0x7F);
+        assert_eq!(read_word(&ram, 0x7F), 0xFFFF);
+        assert_eq!(read_word(&ram, 0x7E), 0x0000);
+
+        // Write all, then protect again: erasing all does nothing.
+        command(&mut eeprom, &mut ram, 0b00, 0x40);
+        shift(&mut eeprom, &mut ram, 0xABCD, 16);
+        command(&mut eeprom, &mut ram, 0b00, 0x00);
+        command(&mut eeprom, &mut ram, 0b00, 0x80);
+        assert!((0..0x80).all(|addr| read_word(&ram, addr) == 0xABCD));
+    }
+}
@@ -0,0 +1,114 @@
+//! The `flash` module emulates the flash memory of MBC6 cartridges.
+//!
+//! Flash reads like ROM, but it takes unlock sequences to program or erase it:
+//! programming only clears bits, and erasing sets them back by whole sectors.
+//! Operations complete instantly, and reading the chip identifiers is not emulated.
+//!
+//! See <https://gbdev.io/pandocs/MBC6.html#flash-commands>
+
+/// Size, in bytes, of the flash memory.
+pub const SIZE: usize = 1024 * 1024;
+/// Size, in bytes, of the area an erase command clears.
+const SECTOR_SIZE: usize = 64 * 1024;
+
+/// Addresses the unlock sequences write to, as seen by the chip.
+const UNLOCK_1: u32 = 0x5555;
+const UNLOCK_2: u32 = 0x2AAA;
+
+/// How far into a command sequence the chip is.
+#[derive(Clone, Copy, Default)]
+enum Mode {
+    #[default]
+    Read,
+    /// 0xAA was written, then 0x55.
+    Unlock1,
+    Unlock2,
+    /// 0x80 was written: an erase command follows after another unlock sequence.
+    Erase,
+    EraseUnlock1,
+    EraseUnlock2,
+    /// 0xA0 was written: the next write programs a byte.
+    Program,
+}
+
+#[derive(Default)]
+pub struct Flash {
+    mode: Mode,
+}
+
+impl Flash {
+    /// Writes to the chip, which only programs memory as part of a command.
+    /// Returns whether `memory` changed.
+    pub fn write(&mut self, memory: &mut [u8], addr: u32, val: u8, write_enabled: bool) -> bool {
+        // The chip only decodes the lower 15 bits of command addresses.
+        let command_addr = addr & 0x7FFF;
+        let mut changed = false;
+        self.mode = match (self.mode, command_addr, val) {
+            (Mode::Program, ..) => {
+                if write_enabled {
+                    memory[addr as usize] &= val;
+                    changed = true;
+                }
+                Mode::Read
+            }
+            (Mode::Read, UNLOCK_1, 0xAA) => Mode::Unlock1,
+            (Mode::Unlock1, UNLOCK_2, 0x55) => Mode::Unlock2,
+            (Mode::Unlock2, UNLOCK_1, 0x80) => Mode::Erase,
+            (Mode::Unlock2, UNLOCK_1, 0xA0) => Mode::Program,
+            (Mode::Erase, UNLOCK_1, 0xAA) => Mode::EraseUnlock1,
+            (Mode::EraseUnlock1, UNLOCK_2, 0x55) => Mode::EraseUnlock2,
+            (Mode::EraseUnlock2, UNLOCK_1, 0x10) => {
+                if write_enabled {
+                    memory.fill(0xFF);
+                    changed = true;
+                }
+                Mode::Read
+            }
+            (Mode::EraseUnlock2, _, 0x30) => {
+                if write_enabled {
+                    let start = addr as usize / SECTOR_SIZE * SECTOR_SIZE;
+                    memory[start..start + SECTOR_SIZE].fill(0xFF);
+                    changed = true;
+                }
+                Mode::Read
+            }
+            // Anything else, 0xF0 included, cancels the command.
+            _ => Mode::Read,
+        };
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unlock(flash: &mut Flash, memory: &mut [u8], command: u8) {
+        flash.write(memory, UNLOCK_1, 0xAA, true);
+        flash.write(memory, UNLOCK_2, 0x55, true);
+        flash.write(memory, UNLOCK_1, command, true);
+    }
+
+    #[test]
+    fn program_then_erase() {
+        let (mut flash, mut memory) = (Flash::default(), vec![0xFF; SIZE]);
+        // Without the unlock sequence, writes are ignored.
+        assert!(!flash.write(&mut memory, 0x12345, 0x00, true));
+
+        unlock(&mut flash, &mut memory, 0xA0);
+        assert!(flash.write(&mut memory, 0x12345, 0x0F, true));
+        unlock(&mut flash, &mut memory, 0xA0);
+        flash.write(&mut memory, 0x12345, 0x3C, true);
+        assert_eq!(memory[0x12345], 0x0C);
+
+        // Writing is not allowed.
+        unlock(&mut flash, &mut memory, 0xA0);
+        assert!(!flash.write(&mut memory, 0x20000, 0x00, false));
+
+        unlock(&mut flash, &mut memory, 0x80);
+        flash.write(&mut memory, UNLOCK_1, 0xAA, true);
+        flash.write(&mut memory, UNLOCK_2, 0x55, true);
+        flash.write(&mut memory, 0x1FFFF, 0x30, true);
+        assert_eq!(memory[0x12345], 0xFF);
+    }
+}
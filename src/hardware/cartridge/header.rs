@@ -5,59 +5,181 @@
 //!
 //! See <https://gbdev.io/pandocs/The_Cartridge_Header.html#the-cartridge-header>
 
-use std::io::{self, Read, Seek};
+use std::io;
 
-use crate::hardware::cartridge::{self, mbc::Mbc};
+use crate::hardware::cartridge::{eeprom, flash, mbc::Mbc, Rom};
+
+/// The Nintendo logo, which the boot ROM checks before starting a game.
+pub const LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+const LOGO_START: usize = 0x104;
 
 #[derive(Clone, Copy)]
 pub struct CartridgeType(u8);
 
 impl CartridgeType {
-    pub fn mbc(&self) -> Mbc {
-        match self.0 {
+    /// Returns the memory controller, or fails if it is not supported.
+    /// Some controllers are told apart by looking at the ROM.
+    pub fn mbc(&self, data: &[u8]) -> io::Result<Mbc> {
+        Ok(match self.0 {
             0x00 | 0x08..=0x09 => Mbc::Mbc0,
-            0x01..=0x03 => Mbc::Mbc1,
+            0x01..=0x03 => Mbc::Mbc1 {
+                multicart: is_multicart(data),
+                banks: Default::default(),
+            },
             0x05..=0x06 => Mbc::Mbc2,
+            0x0B..=0x0D => Mbc::Mmm01(Default::default()),
             0x0F..=0x13 => Mbc::Mbc3,
-            _ => todo!(),
+            0x19..=0x1B => Mbc::Mbc5 { rumble: false },
+            0x1C..=0x1E => Mbc::Mbc5 { rumble: true },
+            0x20 => Mbc::Mbc6(Default::default()),
+            0x22 => Mbc::Mbc7(Default::default()),
+            0xFC => Mbc::Camera(Default::default()),
+            0xFE => Mbc::Huc3(Default::default()),
+            0xFF => Mbc::Huc1 { ir_mode: false },
+            kind => return Err(unsupported("cartridge type", kind)),
+        })
+    }
+
+    /// Returns the size, in bytes, of RAM built into the controller, if any.
+    /// MBC2 has 512 half-bytes of RAM, each taking a byte, and MBC7 keeps its EEPROM there.
+    /// The Pocket Camera always has 128 KiB, where pictures are stored, whatever the header says.
+    pub fn builtin_ram(&self) -> Option<usize> {
+        match self.0 {
+            0x05..=0x06 => Some(512),
+            0x22 => Some(eeprom::SIZE),
+            0xFC => Some(128 * 1024),
+            _ => None,
         }
     }
 
+    /// Returns the size, in bytes, of flash memory, which MBC6 keeps after RAM.
+    pub fn flash(&self) -> Option<usize> {
+        (self.0 == 0x20).then_some(flash::SIZE)
+    }
+
+    pub fn has_rtc(&self) -> bool {
+        [0x0F, 0x10].contains(&self.0)
+    }
+
     pub fn has_battery(&self) -> bool {
-        [0x03, 0x06, 0x09, 0x0D, 0x0F, 0x10, 0x13, 0x1B, 0x1E, 0x22].contains(&self.0)
+        [
+            0x03, 0x06, 0x09, 0x0D, 0x0F, 0x10, 0x13, 0x1B, 0x1E, 0x20, 0x22, 0xFC, 0xFE, 0xFF,
+        ]
+        .contains(&self.0)
+    }
+}
+
+/// Returns whether an MBC1 ROM is a multicart compilation (MBC1M). Their header
+/// says nothing about it, but each game comes with its own header, at a 256 KiB boundary:
+/// so a logo past the first boundary gives a compilation away.
+///
+/// See <https://gbdev.io/pandocs/MBC1.html#mbc1m-1-mib-multi-game-compilation-carts>
+fn is_multicart(data: &[u8]) -> bool {
+    const GAME_SIZE: usize = 16 * Rom::BANK_SIZE as usize;
+    // All known compilations are 1 MiB.
+    data.len() == 4 * GAME_SIZE
+        && data
+            .chunks_exact(GAME_SIZE)
+            .skip(1)
+            .any(|game| game[LOGO_START..LOGO_START + LOGO.len()] == LOGO)
+}
+
+/// Returns the data the header is read from. MMM01 compilations boot into a menu
+/// in the last 32 KiB of the ROM, whose header describes the cartridge:
+/// the one at the start belongs to the first game.
+///
+/// See <https://gbdev.io/pandocs/MMM01.html>
+fn header(data: &[u8]) -> &[u8] {
+    let menu_start = data.len().saturating_sub(2 * Rom::BANK_SIZE as usize);
+    let menu = &data[menu_start..];
+    let is_mmm01 = menu
+        .get(0x147)
+        .is_some_and(|kind| (0x0B..=0x0D).contains(kind));
+    if is_mmm01 && menu.get(LOGO_START..LOGO_START + LOGO.len()) == Some(&LOGO) {
+        menu
+    } else {
+        data
     }
 }
 
-pub fn cartridge_type<R: Read + Seek>(data: &mut R) -> io::Result<CartridgeType> {
-    cartridge::read_at(data, 0x147).map(|code| CartridgeType(code))
+pub fn cartridge_type(data: &[u8]) -> io::Result<CartridgeType> {
+    read_at(header(data), 0x147).map(CartridgeType)
 }
 
-pub fn rom_banks<R: Read + Seek>(data: &mut R) -> io::Result<u8> {
-    Ok(match cartridge::read_at(data, 0x148)? {
-        code if (0x00..=0x08).contains(&code) => (1 << code) + 1,
+pub fn rom_banks(data: &[u8]) -> io::Result<u16> {
+    Ok(match read_at(header(data), 0x148)? {
+        code if (0x00..=0x08).contains(&code) => 2 << code,
         0x52 => 72,
         0x53 => 80,
         0x54 => 96,
-        _ => unreachable!(),
+        code => return Err(unsupported("ROM size code", code)),
     })
 }
 
-pub fn ram_banks<R: Read + Seek>(data: &mut R, cartridge_type: CartridgeType) -> io::Result<u8> {
-    Ok(match cartridge::read_at(data, 0x149)? {
-        0x00 => {
-            // MBC2 has 512 half-bytes or RAM, but it's internal, so ram_banks
-            // is technically zero. However, we don't emulate the hardware layout precisely,
-            // so we pretend it has 1 bank of regular RAM.
-            if (0x05..=0x06).contains(&cartridge_type.0) {
-                1
-            } else {
-                0
-            }
-        }
-        0x02 => 1,
-        0x03 => 4,
-        0x04 => 16,
-        0x05 => 8,
-        _ => unreachable!(),
+/// Returns the size, in bytes, of RAM on the cartridge, not counting RAM built into the controller.
+pub fn ram_size(data: &[u8]) -> io::Result<usize> {
+    const KIB: usize = 1024;
+    Ok(match read_at(header(data), 0x149)? {
+        0x00 => 0,
+        0x01 => 2 * KIB,
+        0x02 => 8 * KIB,
+        0x03 => 32 * KIB,
+        0x04 => 128 * KIB,
+        0x05 => 64 * KIB,
+        code => return Err(unsupported("RAM size code", code)),
     })
 }
+
+/// Builds the error for a header field whose value is not supported.
+fn unsupported(field: &str, val: u8) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unsupported {field} 0x{val:02X}"),
+    )
+}
+
+/// Reads a byte of the header, failing if the ROM is too short to contain it.
+fn read_at(data: &[u8], addr: usize) -> io::Result<u8> {
+    data.get(addr).copied().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "the ROM is too short to contain a header",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::hardware::{cartridge::test_rom, Cartridge};
+
+    #[test]
+    fn unsupported_values_fail() {
+        for rom in [
+            test_rom(0xFD, 0x00, 0x00, 0x4000),
+            test_rom(0x04, 0x00, 0x00, 0x4000),
+            test_rom(0x01, 0x00, 0x06, 0x4000),
+        ] {
+            let err = Cartridge::from_rom(rom).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        let err = Cartridge::from_rom(test_rom(0xFD, 0x00, 0x00, 0x4000))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "unsupported cartridge type 0xFD");
+    }
+
+    #[test]
+    fn two_kib_of_ram() {
+        let mut cart = Cartridge::from_rom(test_rom(0x02, 0x00, 0x01, 0x4000)).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA7FF, 0x42);
+        assert_eq!(cart.read(0xA7FF), 0x42);
+        assert_eq!(cart.read(0xA800), 0xFF);
+    }
+}
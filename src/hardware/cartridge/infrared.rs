@@ -0,0 +1,72 @@
+//! The `infrared` module defines the infrared port some cartridges carry,
+//! which games use to exchange data with another Game Boy, or with toys.
+//!
+//! The cartridge only drives its LED and samples its sensor: whatever is on the other side,
+//! a peer emulator, a network link or a test double, is provided through [`Infrared`].
+//!
+//! See <https://gbdev.io/pandocs/HuC1.html>
+
+use crate::hardware::cartridge::Hardware;
+
+/// The far side of an infrared port.
+pub trait Infrared {
+    /// Turns the LED of the cartridge on or off.
+    fn set_led(&mut self, on: bool);
+    /// Returns whether the sensor of the cartridge sees light, that is, whether the LED
+    /// of the peer is on.
+    fn sees_light(&self) -> bool;
+}
+
+impl Hardware {
+    /// Reads the sensor, as the `0xA000–0xBFFF` range does in infrared mode.
+    /// Without a peer, no light is ever seen.
+    pub fn read_infrared(&self) -> u8 {
+        0xC0 | self.infrared.as_ref().is_some_and(|port| port.sees_light()) as u8
+    }
+
+    /// Drives the LED, as the `0xA000–0xBFFF` range does in infrared mode.
+    pub fn write_infrared(&mut self, val: u8) {
+        if let Some(port) = self.infrared.as_mut() {
+            port.set_led(val & 0b00000001 != 0);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// One end of two ports facing each other: each sensor sees the LED of the other end.
+    pub struct Peer {
+        own_led: Rc<Cell<bool>>,
+        other_led: Rc<Cell<bool>>,
+    }
+
+    impl Peer {
+        pub fn pair() -> (Self, Self) {
+            let (a, b) = (Rc::new(Cell::new(false)), Rc::new(Cell::new(false)));
+            (
+                Self {
+                    own_led: a.clone(),
+                    other_led: b.clone(),
+                },
+                Self {
+                    own_led: b,
+                    other_led: a,
+                },
+            )
+        }
+    }
+
+    impl Infrared for Peer {
+        fn set_led(&mut self, on: bool) {
+            self.own_led.set(on);
+        }
+
+        fn sees_light(&self) -> bool {
+            self.other_led.get()
+        }
+    }
+}
@@ -1,36 +1,98 @@
+mod camera;
+mod huc1;
+mod huc3;
 mod mbc0;
 mod mbc1;
 mod mbc2;
 mod mbc3;
-
-use std::io;
+mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
 
 use crate::hardware::cartridge::Hardware;
 
 /// The logic that a cartridge follows according to its hardware.
 pub enum Mbc {
     Mbc0,
-    Mbc1, // NOTE: this does not emulate MBC1M.
+    Mbc1 {
+        /// Whether the cartridge is an MBC1M, a multicart wired to address
+        /// only 16 banks with the lower bank register.
+        multicart: bool,
+        banks: mbc1::Banks,
+    },
     Mbc2,
     Mbc3,
+    Mbc5 {
+        /// Whether the cartridge has a rumble motor, wired to a bit of the RAM bank selector.
+        rumble: bool,
+    },
+    Mbc6(mbc6::Mbc6),
+    Mbc7(mbc7::Mbc7),
+    Mmm01(mmm01::Banks),
+    Huc1 {
+        /// Whether 0xA000–0xBFFF maps the infrared port instead of RAM.
+        ir_mode: bool,
+    },
+    /// Boxed, as the microcontroller memory makes it much larger than the others.
+    Huc3(Box<huc3::Huc3>),
+    Camera(camera::Camera),
 }
 
 impl Mbc {
-    pub fn read(&self, mem: &mut Hardware, addr: u16) -> io::Result<u8> {
+    pub fn read(&self, mem: &Hardware, addr: u16) -> u8 {
         match self {
             Self::Mbc0 => mbc0::read(mem, addr),
-            Self::Mbc1 => mbc1::read(mem, addr),
+            Self::Mbc1 { multicart, banks } => mbc1::read(mem, addr, banks, *multicart),
             Self::Mbc2 => mbc2::read(mem, addr),
             Self::Mbc3 => mbc3::read(mem, addr),
+            Self::Mbc5 { .. } => mbc5::read(mem, addr),
+            Self::Mbc6(mbc6) => mbc6::read(mem, addr, mbc6),
+            Self::Mbc7(mbc7) => mbc7::read(mem, addr, mbc7),
+            Self::Mmm01(banks) => mmm01::read(mem, addr, banks),
+            Self::Huc1 { ir_mode } => huc1::read(mem, addr, *ir_mode),
+            Self::Huc3(huc3) => huc3::read(mem, addr, huc3),
+            Self::Camera(camera) => camera::read(mem, addr, camera),
         }
     }
 
     pub fn write(&mut self, mem: &mut Hardware, addr: u16, val: u8) {
         match self {
             Self::Mbc0 => (),
-            Self::Mbc1 => mbc1::write(mem, addr, val),
+            Self::Mbc1 { multicart, banks } => mbc1::write(mem, addr, val, banks, *multicart),
             Self::Mbc2 => mbc2::write(mem, addr, val),
             Self::Mbc3 => mbc3::write(mem, addr, val),
+            Self::Mbc5 { rumble } => mbc5::write(mem, addr, val, *rumble),
+            Self::Mbc6(mbc6) => mbc6::write(mem, addr, val, mbc6),
+            Self::Mbc7(mbc7) => mbc7::write(mem, addr, val, mbc7),
+            Self::Mmm01(banks) => mmm01::write(mem, addr, val, banks),
+            Self::Huc1 { ir_mode } => huc1::write(mem, addr, val, ir_mode),
+            Self::Huc3(huc3) => huc3::write(mem, addr, val, huc3),
+            Self::Camera(camera) => camera::write(mem, addr, val, camera),
+        }
+    }
+
+    /// Returns the state of the controller to save after RAM, if it keeps any.
+    pub fn footer(&self) -> Option<Vec<u8>> {
+        match self {
+            Self::Huc3(huc3) => Some(huc3.footer().to_vec()),
+            _ => None,
+        }
+    }
+
+    /// Restores the state of the controller from the footer of a save. See [`Self::footer`].
+    pub fn restore(&mut self, footer: &[u8]) {
+        if let Self::Huc3(huc3) = self {
+            huc3.restore(footer);
+        }
+    }
+
+    /// Advances clocks and sensors inside the controller, if any.
+    pub fn tick(&mut self, mem: &mut Hardware, ticks: u8) {
+        match self {
+            Self::Huc3(huc3) => huc3.tick(ticks),
+            Self::Camera(camera) => camera::tick(mem, ticks, camera),
+            _ => (),
         }
     }
 }
@@ -0,0 +1,196 @@
+//! The Pocket Camera controller maps 128 KiB of RAM, and the registers of its image sensor
+//! in place of RAM when bit 4 of the RAM bank is set. Pictures are taken in the background,
+//! and land in RAM bank 0.
+//!
+//! See <https://gbdev.io/pandocs/Gameboy_Camera.html>
+
+use crate::hardware::{
+    cartridge::{
+        sensor::{self, Settings},
+        Hardware,
+    },
+    M_CYCLE,
+};
+
+/// Number of sensor registers, mirrored every 0x80 bytes.
+const REGISTERS: usize = 0x36;
+/// Where pictures are stored, in RAM bank 0.
+const IMAGE_START: usize = 0x0100;
+
+pub struct Camera {
+    /// Whether 0xA000–0xBFFF maps the sensor registers instead of RAM.
+    registers_mapped: bool,
+    registers: [u8; REGISTERS],
+    /// Clock ticks left before the picture being taken, if any, is ready.
+    capture_ticks: u32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            registers_mapped: false,
+            registers: [0; REGISTERS],
+            capture_ticks: 0,
+        }
+    }
+}
+
+impl Camera {
+    fn capturing(&self) -> bool {
+        self.capture_ticks > 0
+    }
+
+    /// Reads a register. Only the first one can be read back, the others read 0.
+    fn read_register(&self, idx: usize) -> u8 {
+        match idx {
+            0 => self.registers[0] & 0b00000110 | self.capturing() as u8,
+            _ => 0x00,
+        }
+    }
+
+    fn write_register(&mut self, idx: usize, val: u8) {
+        match idx {
+            0 => {
+                self.registers[0] = val & 0b00000111;
+                if val & 1 != 0 && !self.capturing() {
+                    self.capture_ticks = self.capture_duration();
+                }
+            }
+            1..REGISTERS => self.registers[idx] = val,
+            _ => (),
+        }
+    }
+
+    /// Returns how long taking a picture takes, in clock ticks. It depends on the exposure.
+    fn capture_duration(&self) -> u32 {
+        let exclusive = self.registers[1] & 0b10000000 != 0;
+        let cycles = 32446 + if exclusive { 0 } else { 512 } + 16 * self.exposure() as u32;
+        cycles * M_CYCLE as u32
+    }
+
+    fn exposure(&self) -> u16 {
+        u16::from_be_bytes([self.registers[2], self.registers[3]])
+    }
+
+    fn settings(&self) -> Settings<'_> {
+        Settings {
+            exposure: self.exposure(),
+            horizontal: self.registers[1] & 0b00100000 != 0,
+            vertical: self.registers[1] & 0b01000000 != 0,
+            edge_ratio: (self.registers[4] >> 4) & 0b111,
+            invert: self.registers[4] & 0b00001000 != 0,
+            matrix: &self.registers[6..REGISTERS],
+        }
+    }
+}
+
+/// Advances the picture being taken, if any, and stores it once done.
+pub fn tick(hw: &mut Hardware, ticks: u8, camera: &mut Camera) {
+    if !camera.capturing() {
+        return;
+    }
+    camera.capture_ticks = camera.capture_ticks.saturating_sub(ticks as u32);
+    if camera.capturing() {
+        return;
+    }
+    // Without a source, the sensor sees no light.
+    let scene = hw
+        .image_source
+        .as_mut()
+        .map_or([[0; sensor::WIDTH]; sensor::HEIGHT], |source| {
+            source.capture()
+        });
+    let image = sensor::process(&scene, &camera.settings());
+    hw.ram.data[IMAGE_START..IMAGE_START + sensor::IMAGE_SIZE].copy_from_slice(&image);
+    hw.ram.dirty = true;
+}
+
+pub fn read(hw: &Hardware, addr: u16, camera: &Camera) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
+        0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
+        0xA000..=0xBFFF if camera.registers_mapped => {
+            camera.read_register((addr & 0x007F) as usize)
+        }
+        // RAM cannot be read while the sensor writes it.
+        0xA000..=0xBFFF if camera.capturing() => 0x00,
+        0xA000..=0xBFFF => hw.ram.read_current_bank(addr - 0xA000),
+        _ => unreachable!(),
+    }
+}
+
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, camera: &mut Camera) {
+    match addr {
+        0x0000..=0x1FFF => hw.ram.enabled = val & 0x0F == 0x0A,
+        0x2000..=0x3FFF => hw.rom.set_bank(val & 0b00111111),
+        0x4000..=0x5FFF => {
+            camera.registers_mapped = val & 0b00010000 != 0;
+            hw.ram.set_current_bank_wide(val);
+        }
+        0x6000..=0x7FFF => (), // Not connected to the controller.
+        // Registers can be written even with RAM disabled.
+        0xA000..=0xBFFF if camera.registers_mapped => {
+            camera.write_register((addr & 0x007F) as usize, val)
+        }
+        0xA000..=0xBFFF if camera.capturing() => (),
+        0xA000..=0xBFFF => hw.ram.write_current_bank(addr - 0xA000, val),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{
+        cartridge::{
+            sensor::tests::{shade, Stripes},
+            test_rom,
+        },
+        Cartridge,
+    };
+
+    /// Builds a camera whose header claims no RAM, which does not change how much it has.
+    fn cartridge() -> Cartridge {
+        let mut cart = Cartridge::from_rom(test_rom(0xFC, 0x05, 0x00, 0x4000)).unwrap();
+        cart.set_image_source(Box::new(Stripes));
+        cart
+    }
+
+    #[test]
+    fn capture() {
+        let mut cart = cartridge();
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x10);
+        // Neutral exposure, and the same thresholds everywhere in the matrix.
+        cart.write(0xA002, 0x08);
+        cart.write(0xA003, 0x00);
+        for cell in 0..16 {
+            for (idx, threshold) in [0x40, 0x80, 0xC0].into_iter().enumerate() {
+                cart.write(0xA006 + cell * 3 + idx as u16, threshold);
+            }
+        }
+        // Registers are mirrored.
+        cart.write(0xA080, 0x01);
+        assert_eq!(cart.read(0xA000), 0x01);
+
+        while cart.read(0xA000) & 1 != 0 {
+            cart.tick(4);
+        }
+
+        cart.write(0x4000, 0x00);
+        let image: Vec<u8> = (0..0xE00).map(|idx| cart.read(0xA100 + idx)).collect();
+        let shades: Vec<u8> = (0..4).map(|stripe| shade(&image, stripe * 32, 0)).collect();
+        assert_eq!(shades, [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn ram_banks() {
+        let mut cart = cartridge();
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x0F);
+        cart.write(0xA000, 0x42);
+        cart.write(0x4000, 0x00);
+        assert_eq!(cart.read(0xA000), 0x00);
+        cart.write(0x4000, 0x0F);
+        assert_eq!(cart.read(0xA000), 0x42);
+    }
+}
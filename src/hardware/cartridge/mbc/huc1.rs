@@ -0,0 +1,57 @@
+use crate::hardware::cartridge::Hardware;
+
+pub fn read(hw: &Hardware, addr: u16, ir_mode: bool) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
+        0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
+        0xA000..=0xBFFF if ir_mode => hw.read_infrared(),
+        0xA000..=0xBFFF => hw.ram.read_current_bank(addr - 0xA000),
+        _ => unreachable!(),
+    }
+}
+
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, ir_mode: &mut bool) {
+    match addr {
+        0x0000..=0x1FFF => {
+            // There is no RAM enable: the area shows either RAM or the infrared port.
+            *ir_mode = val == 0x0E;
+            hw.ram.enabled = !*ir_mode;
+        }
+        0x2000..=0x3FFF => hw.rom.set_bank(val & 0b00111111),
+        0x4000..=0x5FFF => hw.ram.set_current_bank(val),
+        0x6000..=0x7FFF => (), // Not connected to the controller.
+        0xA000..=0xBFFF if *ir_mode => hw.write_infrared(val),
+        0xA000..=0xBFFF => hw.ram.write_current_bank(addr - 0xA000, val),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{
+        cartridge::{infrared::tests::Peer, test_rom, Infrared},
+        Cartridge,
+    };
+
+    #[test]
+    fn infrared() {
+        let mut cart = Cartridge::from_rom(test_rom(0xFF, 0x01, 0x02, 0x4000)).unwrap();
+        let (port, mut peer) = Peer::pair();
+        cart.set_infrared(Box::new(port));
+
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x01);
+        assert_eq!(cart.read(0xA000), 0x01);
+
+        cart.write(0x0000, 0x0E);
+        assert_eq!(cart.read(0xA000), 0xC0);
+        peer.set_led(true);
+        assert_eq!(cart.read(0xA000), 0xC1);
+        cart.write(0xA000, 0x01);
+        assert!(peer.sees_light());
+
+        // RAM is left untouched by the infrared port.
+        cart.write(0x0000, 0x00);
+        assert_eq!(cart.read(0xA000), 0x01);
+    }
+}
@@ -0,0 +1,294 @@
+//! HuC3 maps its clock and infrared port through a mode register. The clock is not read
+//! directly: the game sends commands to a small microcontroller, which answers a nibble at a time.
+//!
+//! See <https://gbdev.io/pandocs/HuC3.html>
+
+use crate::hardware::{cartridge::Hardware, MASTER_CLOCK};
+
+const TICKS_PER_MINUTE: u32 = 60 * MASTER_CLOCK;
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Addresses of the clock in the memory of the microcontroller: 3 nibbles of minutes,
+/// then 4 nibbles of days, least significant first.
+const CLOCK_MINUTES: usize = 0;
+const CLOCK_DAYS: usize = 3;
+/// Size, in bytes, of the footer appended to saves: minutes and days,
+/// then the memory of the microcontroller.
+const FOOTER_SIZE: usize = 4 + 256;
+
+pub struct Huc3 {
+    /// What 0xA000–0xBFFF maps to, as written to 0x0000–0x1FFF.
+    mode: u8,
+    /// Minutes elapsed in the current day.
+    minutes: u16,
+    days: u16,
+    /// Clock ticks elapsed since the last minute.
+    ticks: u32,
+    /// Memory of the microcontroller, one nibble per address.
+    memory: [u8; 256],
+    /// The address the next command accesses.
+    pointer: u8,
+    /// The last command received, and its result.
+    command: u8,
+    response: u8,
+}
+
+impl Default for Huc3 {
+    fn default() -> Self {
+        Self {
+            mode: 0,
+            minutes: 0,
+            days: 0,
+            ticks: 0,
+            memory: [0; 256],
+            pointer: 0,
+            command: 0,
+            response: 0,
+        }
+    }
+}
+
+impl Huc3 {
+    /// Maps RAM, read-only.
+    const MODE_RAM_READ: u8 = 0x0;
+    /// Maps RAM.
+    const MODE_RAM: u8 = 0xA;
+    /// Writes send commands to the microcontroller.
+    const MODE_COMMAND: u8 = 0xB;
+    /// Reads return the result of the last command.
+    const MODE_RESPONSE: u8 = 0xC;
+    /// Reads return whether the microcontroller is ready for a command.
+    const MODE_SEMAPHORE: u8 = 0xD;
+    const MODE_INFRARED: u8 = 0xE;
+
+    pub fn tick(&mut self, ticks: u8) {
+        self.ticks += ticks as u32;
+        if self.ticks < TICKS_PER_MINUTE {
+            return;
+        }
+        self.ticks -= TICKS_PER_MINUTE;
+        self.minutes += 1;
+        if self.minutes == MINUTES_PER_DAY {
+            self.minutes = 0;
+            self.days = self.days.wrapping_add(1);
+        }
+    }
+
+    /// Returns the footer to append to saves. The clock stops while the emulator is not running.
+    pub fn footer(&self) -> [u8; FOOTER_SIZE] {
+        let mut footer = [0; FOOTER_SIZE];
+        footer[0..2].copy_from_slice(&self.minutes.to_le_bytes());
+        footer[2..4].copy_from_slice(&self.days.to_le_bytes());
+        footer[4..].copy_from_slice(&self.memory);
+        footer
+    }
+
+    /// Restores the clock and memory from the footer of a save, and returns whether it was valid.
+    pub fn restore(&mut self, footer: &[u8]) -> bool {
+        if footer.len() != FOOTER_SIZE {
+            return false;
+        }
+        let minutes = u16::from_le_bytes([footer[0], footer[1]]);
+        self.minutes = minutes % MINUTES_PER_DAY;
+        self.days = u16::from_le_bytes([footer[2], footer[3]]);
+        self.ticks = 0;
+        self.memory.copy_from_slice(&footer[4..]);
+        true
+    }
+
+    /// Executes a command: the upper nibble is the command itself, the lower one its argument.
+    fn execute(&mut self, val: u8) {
+        self.command = (val >> 4) & 0b111;
+        let arg = val & 0x0F;
+        match self.command {
+            // Read and move to the next address.
+            0x1 => {
+                self.response = self.memory[self.pointer as usize];
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            // Write, and move to the next address with 3.
+            0x2 => self.memory[self.pointer as usize] = arg,
+            0x3 => {
+                self.memory[self.pointer as usize] = arg;
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            // Set the lower or upper nibble of the address.
+            0x4 => self.pointer = self.pointer & 0xF0 | arg,
+            0x5 => self.pointer = self.pointer & 0x0F | arg << 4,
+            0x6 => match arg {
+                0x0 => self.store_clock(),
+                0x1 => self.load_clock(),
+                // Status request: the microcontroller is fine.
+                0x2 => self.response = 0x1,
+                _ => (),
+            },
+            _ => (),
+        }
+    }
+
+    /// Copies the clock to memory, where the game can read it.
+    fn store_clock(&mut self) {
+        let nibbles = (0..3)
+            .map(|i| (self.minutes >> (4 * i)) as u8 & 0x0F)
+            .chain((0..4).map(|i| (self.days >> (4 * i)) as u8 & 0x0F));
+        for (cell, nibble) in self.memory[CLOCK_MINUTES..].iter_mut().zip(nibbles) {
+            *cell = nibble;
+        }
+    }
+
+    /// Sets the clock from memory, where the game wrote it.
+    fn load_clock(&mut self) {
+        let value = |cells: &[u8]| {
+            cells
+                .iter()
+                .rev()
+                .fold(0u16, |acc, &nibble| acc << 4 | nibble as u16)
+        };
+        self.minutes = value(&self.memory[CLOCK_MINUTES..CLOCK_DAYS]) % MINUTES_PER_DAY;
+        self.days = value(&self.memory[CLOCK_DAYS..CLOCK_DAYS + 4]);
+        self.ticks = 0;
+    }
+}
+
+pub fn read(hw: &Hardware, addr: u16, huc3: &Huc3) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
+        0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
+        0xA000..=0xBFFF => match huc3.mode {
+            Huc3::MODE_RAM_READ | Huc3::MODE_RAM => hw.ram.read_current_bank(addr - 0xA000),
+            Huc3::MODE_RESPONSE => huc3.command << 4 | huc3.response,
+            // Commands complete instantly, so it is always ready.
+            Huc3::MODE_SEMAPHORE => 0x01,
+            Huc3::MODE_INFRARED => hw.read_infrared(),
+            _ => 0xFF,
+        },
+        _ => unreachable!(),
+    }
+}
+
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, huc3: &mut Huc3) {
+    match addr {
+        0x0000..=0x1FFF => {
+            huc3.mode = val & 0x0F;
+            // RAM is readable in both RAM modes, but only writable in one.
+            hw.ram.enabled = matches!(huc3.mode, Huc3::MODE_RAM_READ | Huc3::MODE_RAM);
+        }
+        0x2000..=0x3FFF => hw.rom.set_bank(val & 0b01111111),
+        0x4000..=0x5FFF => hw.ram.set_current_bank(val),
+        0x6000..=0x7FFF => (), // Not connected to the controller.
+        0xA000..=0xBFFF => match huc3.mode {
+            Huc3::MODE_RAM => hw.ram.write_current_bank(addr - 0xA000, val),
+            Huc3::MODE_COMMAND => huc3.execute(val),
+            Huc3::MODE_INFRARED => hw.write_infrared(val),
+            _ => (),
+        },
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::{
+        cartridge::{infrared::tests::Peer, save::tests::MemoryStorage, test_rom, Infrared},
+        Cartridge,
+    };
+
+    /// Sends a command, and returns the response.
+    fn command(cart: &mut Cartridge, val: u8) -> u8 {
+        cart.write(0x0000, Huc3::MODE_COMMAND);
+        cart.write(0xA000, val);
+        cart.write(0x0000, Huc3::MODE_RESPONSE);
+        cart.read(0xA000)
+    }
+
+    #[test]
+    fn day_rollover() {
+        let mut huc3 = Huc3 {
+            minutes: MINUTES_PER_DAY - 1,
+            ticks: TICKS_PER_MINUTE - 4,
+            ..Default::default()
+        };
+        huc3.tick(4);
+        assert_eq!((huc3.minutes, huc3.days), (0, 1));
+        huc3.tick(4);
+        assert_eq!((huc3.minutes, huc3.days), (0, 1));
+    }
+
+    #[test]
+    fn clock() {
+        let mut cart = Cartridge::from_rom(test_rom(0xFE, 0x01, 0x03, 0x4000)).unwrap();
+
+        // Write 0x123 minutes and 5 days, and set the clock from them.
+        command(&mut cart, 0x40);
+        command(&mut cart, 0x50);
+        for nibble in [3, 2, 1, 5, 0, 0, 0] {
+            command(&mut cart, 0x30 | nibble);
+        }
+        command(&mut cart, 0x61);
+
+        // Clear memory, copy the clock back to it and read it.
+        command(&mut cart, 0x40);
+        for _ in 0..7 {
+            command(&mut cart, 0x30);
+        }
+        command(&mut cart, 0x60);
+        command(&mut cart, 0x40);
+        let nibbles: Vec<u8> = (0..7).map(|_| command(&mut cart, 0x10) & 0x0F).collect();
+        assert_eq!(nibbles, [3, 2, 1, 5, 0, 0, 0]);
+        command(&mut cart, 0x40);
+        assert_eq!(command(&mut cart, 0x10), 0x13);
+
+        assert_eq!(command(&mut cart, 0x62), 0x61);
+        cart.write(0x0000, Huc3::MODE_SEMAPHORE);
+        assert_eq!(cart.read(0xA000), 0x01);
+    }
+
+    #[test]
+    fn saved_clock() {
+        let storage = MemoryStorage::default();
+        let mut cart = Cartridge::from_rom(test_rom(0xFE, 0x01, 0x03, 0x4000)).unwrap();
+        cart.set_storage(Box::new(storage.clone())).unwrap();
+        // Set the clock to 0x123 minutes, and leave a nibble in memory.
+        command(&mut cart, 0x40);
+        command(&mut cart, 0x50);
+        for nibble in [3, 2, 1, 0, 0, 0, 0, 9] {
+            command(&mut cart, 0x30 | nibble);
+        }
+        command(&mut cart, 0x61);
+        cart.save().unwrap();
+        assert_eq!(
+            storage.0.borrow().as_ref().unwrap().len(),
+            0x8000 + FOOTER_SIZE
+        );
+
+        let mut cart = Cartridge::from_rom(test_rom(0xFE, 0x01, 0x03, 0x4000)).unwrap();
+        cart.set_storage(Box::new(storage)).unwrap();
+        command(&mut cart, 0x60);
+        command(&mut cart, 0x40);
+        let nibbles: Vec<u8> = (0..8).map(|_| command(&mut cart, 0x10) & 0x0F).collect();
+        assert_eq!(nibbles, [3, 2, 1, 0, 0, 0, 0, 9]);
+    }
+
+    #[test]
+    fn ram_modes() {
+        let mut cart = Cartridge::from_rom(test_rom(0xFE, 0x01, 0x03, 0x4000)).unwrap();
+        cart.write(0x0000, Huc3::MODE_RAM);
+        cart.write(0xA000, 0x42);
+        cart.write(0x0000, Huc3::MODE_RAM_READ);
+        cart.write(0xA000, 0x43);
+        assert_eq!(cart.read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn infrared() {
+        let mut cart = Cartridge::from_rom(test_rom(0xFE, 0x01, 0x03, 0x4000)).unwrap();
+        let (port, mut peer) = Peer::pair();
+        cart.set_infrared(Box::new(port));
+        cart.write(0x0000, Huc3::MODE_INFRARED);
+        peer.set_led(true);
+        assert_eq!(cart.read(0xA000), 0xC1);
+        cart.write(0xA000, 0x01);
+        assert!(peer.sees_light());
+    }
+}
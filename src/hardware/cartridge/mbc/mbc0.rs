@@ -1,11 +1,9 @@
-use std::io;
-
 use crate::hardware::cartridge::Hardware;
 
-pub fn read(hw: &mut Hardware, addr: u16) -> io::Result<u8> {
+pub fn read(hw: &Hardware, addr: u16) -> u8 {
     match addr {
-        0x0000..=0x7FFF => hw.rom.at(addr),
-        0xA000..=0xBFFF => Ok(hw.ram.read(addr - 0xA000)),
+        0x0000..=0x7FFF => hw.rom.at(addr as u32),
+        0xA000..=0xBFFF => hw.ram.read((addr - 0xA000) as u32),
         _ => unreachable!(),
     }
 }
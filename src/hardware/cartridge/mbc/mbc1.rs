@@ -1,62 +1,144 @@
-use std::io;
-
 use crate::hardware::cartridge::{BankingMode, Hardware};
 
-pub fn read(hw: &mut Hardware, addr: u16) -> io::Result<u8> {
+/// The bank registers, as last written.
+#[derive(Default)]
+pub struct Banks {
+    /// The lower 5 bits of the ROM bank.
+    lower: u8,
+    /// The upper 2 bits of the ROM bank, or the RAM bank, depending on the banking mode.
+    upper: u8,
+}
+
+impl Banks {
+    /// Returns how many bits of the ROM bank the lower register provides.
+    /// MBC1M does not connect its fifth bit, and shifts the upper register down to take its place.
+    fn lower_bits(multicart: bool) -> u8 {
+        if multicart {
+            4
+        } else {
+            5
+        }
+    }
+
+    /// Returns the ROM bank mapped at 0x4000–0x7FFF.
+    fn rom_bank(&self, multicart: bool) -> u16 {
+        // Bank 0 is translated to 1 before the fifth bit is dropped, even on MBC1M.
+        let lower = if self.lower == 0 { 1 } else { self.lower };
+        let bits = Self::lower_bits(multicart);
+        (self.upper as u16) << bits | (lower & ((1 << bits) - 1)) as u16
+    }
+
+    /// Returns the ROM bank mapped at 0x0000–0x3FFF, which the upper register
+    /// selects in RAM banking mode. Multicarts use it to start their games.
+    fn zero_bank(&self, hw: &Hardware, multicart: bool) -> u16 {
+        match hw.banking_mode {
+            BankingMode::Ram => (self.upper as u16) << Self::lower_bits(multicart),
+            _ => 0,
+        }
+    }
+
+    /// Maps the banks the registers select.
+    fn apply(&self, hw: &mut Hardware, multicart: bool) {
+        hw.rom.set_bank_unchecked(self.rom_bank(multicart));
+        // With a single bank of RAM, the upper register is not connected to it.
+        let ram_bank = match hw.banking_mode {
+            BankingMode::Ram if hw.ram.banks > 1 => self.upper,
+            _ => 0,
+        };
+        hw.ram.set_current_bank(ram_bank);
+    }
+}
+
+pub fn read(hw: &Hardware, addr: u16, banks: &Banks, multicart: bool) -> u8 {
     match addr {
-        0x0000..=0x3FFF => hw.rom.at(addr),
+        0x0000..=0x3FFF => hw.rom.at_bank(banks.zero_bank(hw, multicart), addr),
         0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
-        0xA000..=0xBFFF => Ok(hw.ram.read_current_bank(addr - 0xA000)),
+        0xA000..=0xBFFF => hw.ram.read_current_bank(addr - 0xA000),
         _ => unreachable!(),
     }
 }
 
-pub fn write(hw: &mut Hardware, addr: u16, val: u8) {
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, banks: &mut Banks, multicart: bool) {
     match addr {
         0x0000..=0x1FFF => hw.ram.enabled = val & 0x0F == 0x0A, // Any value with 0xA in the lower 4 bits enables the ram.
-        0x2000..=0x3FFF => hw.rom.set_bank(val & 0b00011111), // The bank is addressed by 5 bits only.
+        0x2000..=0x3FFF => {
+            banks.lower = val & 0b00011111; // The bank is addressed by 5 bits only.
+            banks.apply(hw, multicart);
+        }
         0x4000..=0x5FFF => {
             // This value is either the ROM bank selector (upper 2 bits),
             // if at least 64 ROM banks are present, or the RAM bank selector.
-            if hw.rom.banks <= 32 && hw.ram.banks <= 1 {
-                // This cartridge is too small for bank selection to be meaningful.
-                return;
-            }
-            match hw.banking_mode {
-                BankingMode::Rom => hw.rom.set_bank_extended(val),
-                BankingMode::Ram => hw.ram.set_current_bank(val),
-                _ => unreachable!(),
-            }
+            banks.upper = val & 0b00000011;
+            banks.apply(hw, multicart);
         }
         0x6000..=0x7FFF => {
-            if hw.rom.banks <= 32 && hw.ram.banks <= 1 {
-                // This cartridge is too small for banking mode to be meaningful.
-                return;
-            }
-            let new_mode = if val == 0 {
+            hw.banking_mode = if val & 0b00000001 == 0 {
                 BankingMode::Rom
             } else {
                 BankingMode::Ram
             };
-            if hw.banking_mode == new_mode {
-                return;
-            }
-            match new_mode {
-                BankingMode::Rom => {
-                    hw.rom.set_bank_extended(hw.ram.curr_bank);
-                    hw.ram.set_current_bank(0);
-                }
-                BankingMode::Ram => {
-                    hw.ram.set_current_bank(hw.rom.curr_bank & 0b11000000 >> 6);
-                    hw.rom.set_bank_extended(0);
-                }
-                _ => unreachable!(),
-            }
-            hw.banking_mode = new_mode;
+            banks.apply(hw, multicart);
         }
         0xA000..=0xBFFF => {
-            hw.ram.write_current_bank(addr - 0x4000, val);
+            hw.ram.write_current_bank(addr - 0xA000, val);
         }
         _ => unreachable!(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{
+        cartridge::{header::LOGO, mbc::Mbc, test_rom},
+        Cartridge,
+    };
+
+    #[test]
+    fn upper_bits_above_bank_31() {
+        let mut cart = Cartridge::from_rom(test_rom(0x01, 0x05, 0x00, 0x4000)).unwrap();
+        cart.write(0x2000, 0x00);
+        cart.write(0x4000, 0x01);
+        assert_eq!(cart.read(0x4000), 0x21);
+        // Only RAM banking mode applies the upper bits to the first half.
+        assert_eq!(cart.read(0x0000), 0x00);
+        cart.write(0x6000, 0x01);
+        assert_eq!(cart.read(0x0000), 0x20);
+    }
+
+    #[test]
+    fn multicart() {
+        let mut rom = test_rom(0x01, 0x05, 0x00, 0x4000);
+        for game in 0..4 {
+            let header = game * 0x40000 + 0x104;
+            rom[header..header + 48].copy_from_slice(&LOGO);
+        }
+        let mut cart = Cartridge::from_rom(rom).unwrap();
+        assert!(matches!(
+            cart.mbc,
+            Mbc::Mbc1 {
+                multicart: true,
+                ..
+            }
+        ));
+
+        // The fifth bit of the lower register is ignored, but still counts for bank 0.
+        cart.write(0x2000, 0x12);
+        assert_eq!(cart.read(0x4000), 0x02);
+        cart.write(0x2000, 0x10);
+        assert_eq!(cart.read(0x4000), 0x00);
+
+        // The menu starts the third game.
+        cart.write(0x4000, 0x02);
+        cart.write(0x6000, 0x01);
+        assert_eq!(cart.read(0x0000), 0x20);
+        cart.write(0x2000, 0x01);
+        assert_eq!(cart.read(0x4000), 0x21);
+    }
+
+    #[test]
+    fn plain_1mib_rom_is_not_a_multicart() {
+        let mut cart = Cartridge::from_rom(test_rom(0x01, 0x05, 0x00, 0x4000)).unwrap();
+        cart.write(0x2000, 0x12);
+        assert_eq!(cart.read(0x4000), 0x12);
+    }
+}
@@ -1,13 +1,14 @@
-use std::io;
-
 use crate::hardware::cartridge::Hardware;
 
-pub fn read(hw: &mut Hardware, addr: u16) -> io::Result<u8> {
+/// Address lines of the built-in RAM, which mirrors across 0xA000–0xBFFF.
+const RAM_MASK: u16 = 0x01FF;
+
+pub fn read(hw: &Hardware, addr: u16) -> u8 {
     match addr {
-        0x0000..=0x3FFF => hw.rom.at(addr),
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
         0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
-        0xA000..=0xA1FF => Ok(hw.ram.read(addr - 0xA000)),
-        0xA200..=0xBFFF => Ok(hw.ram.read(addr - 0xA200)), // Just echoes the above.
+        // RAM cells are 4 bits wide: the upper half of the data bus is left floating.
+        0xA000..=0xBFFF => 0xF0 | hw.ram.read((addr & RAM_MASK) as u32),
         _ => unreachable!(),
     }
 }
@@ -15,16 +16,50 @@ pub fn read(hw: &mut Hardware, addr: u16) -> io::Result<u8> {
 pub fn write(hw: &mut Hardware, addr: u16, val: u8) {
     match addr {
         0x0000..=0x3FFF => {
-            // The LSB of the address controls whether
+            // Bit 8 of the address controls whether
             // we are going to set ROM or RAM.
-            if addr & 0b00000001 == 0 {
-                hw.ram.enabled = val == 0x0A;
+            if addr & 0x0100 == 0 {
+                hw.ram.enabled = val & 0x0F == 0x0A;
             } else {
                 hw.rom.set_bank(val & 0b00001111);
             }
         }
-        0xA000..=0xA1FF => hw.ram.write(addr - 0xA000, val),
-        0xA200..=0xBFFF => hw.ram.write(addr - 0xA200, val), // Just echoes the above.
+        0x4000..=0x7FFF => (), // Not connected to the controller.
+        0xA000..=0xBFFF => hw.ram.write((addr & RAM_MASK) as u32, val & 0x0F),
         _ => unreachable!(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{cartridge::test_rom, Cartridge};
+
+    #[test]
+    fn nibble_ram() {
+        let mut cart = Cartridge::from_rom(test_rom(0x06, 0x03, 0x00, 0x4000)).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x5C);
+        assert_eq!(cart.read(0xA000), 0xFC);
+        // 512 cells mirror across the whole area.
+        assert_eq!(cart.read(0xA200), 0xFC);
+        assert_eq!(cart.read(0xBE00), 0xFC);
+        cart.write(0xA3FF, 0x03);
+        assert_eq!(cart.read(0xA1FF), 0xF3);
+    }
+
+    #[test]
+    fn address_bit_8_selects_the_register() {
+        let mut cart = Cartridge::from_rom(test_rom(0x06, 0x03, 0x00, 0x4000)).unwrap();
+        // Bit 8 set: ROM bank.
+        cart.write(0x2100, 0x0A);
+        assert_eq!(cart.read(0x4000), 0x0A);
+        cart.write(0x0100, 0x00);
+        assert_eq!(cart.read(0x4000), 0x01);
+
+        // Bit 8 clear: RAM enable.
+        cart.write(0x3E01, 0x0A);
+        cart.write(0xA000, 0x01);
+        assert_eq!(cart.read(0xA000), 0xF1);
+        assert_eq!(cart.read(0x4000), 0x01);
+    }
+}
@@ -1,15 +1,13 @@
-use std::io;
-
 use crate::hardware::cartridge::{BankingMode, Hardware};
 
-pub fn read(hw: &mut Hardware, addr: u16) -> io::Result<u8> {
+pub fn read(hw: &Hardware, addr: u16) -> u8 {
     match addr {
-        0x0000..=0x3FFF => hw.rom.at(addr),
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
         0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
         0xA000..=0xBFFF => match hw.banking_mode {
-            BankingMode::Ram => Ok(hw.ram.read_current_bank(addr - 0xA000)),
-            BankingMode::Rtc => Ok(hw.rtc.read_current_register()),
-            _ => unreachable!(),
+            // RAM bank 0 is selected until the game selects something else.
+            BankingMode::Rom | BankingMode::Ram => hw.ram.read_current_bank(addr - 0xA000),
+            BankingMode::Rtc => hw.rtc.read_current_register(),
         },
         _ => unreachable!(),
     }
@@ -32,11 +30,10 @@ pub fn write(hw: &mut Hardware, addr: u16, val: u8) {
             }
         }
         0xA000..=0xBFFF => match hw.banking_mode {
-            BankingMode::Ram => hw.ram.write_current_bank(addr - 0xA000, val),
+            BankingMode::Rom | BankingMode::Ram => hw.ram.write_current_bank(addr - 0xA000, val),
             BankingMode::Rtc => hw.rtc.write_current_register(val),
-            _ => unreachable!(),
         },
-        0x6000..=0x7FFF => hw.rtc.set_latched(val != 0),
+        0x6000..=0x7FFF => hw.rtc.write_latch(val),
         _ => unreachable!(),
     }
 }
@@ -0,0 +1,92 @@
+use crate::hardware::cartridge::Hardware;
+
+pub fn read(hw: &Hardware, addr: u16) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
+        0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
+        0xA000..=0xBFFF => hw.ram.read_current_bank(addr - 0xA000),
+        _ => unreachable!(),
+    }
+}
+
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, rumble: bool) {
+    match addr {
+        0x0000..=0x1FFF => hw.ram.enabled = val == 0x0A, // Unlike MBC1, all 8 bits are checked.
+        // The ROM bank is 9 bits wide, split in two registers. Unlike MBC1, bank 0 can be selected.
+        0x2000..=0x2FFF => hw
+            .rom
+            .set_bank_unchecked(hw.rom.curr_bank & 0x100 | val as u16),
+        0x3000..=0x3FFF => hw
+            .rom
+            .set_bank_unchecked(hw.rom.curr_bank & 0xFF | (val as u16 & 1) << 8),
+        0x4000..=0x5FFF => {
+            if rumble {
+                // Bit 3 drives the motor instead of selecting RAM banks.
+                let motor = val & 0b00001000 != 0;
+                hw.motor_changed |= motor != hw.motor;
+                hw.motor = motor;
+                hw.ram.set_current_bank_wide(val & 0b00000111);
+            } else {
+                hw.ram.set_current_bank_wide(val);
+            }
+        }
+        0x6000..=0x7FFF => (), // Not connected to the controller.
+        0xA000..=0xBFFF => hw.ram.write_current_bank(addr - 0xA000, val),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{cartridge::test_rom, Cartridge};
+
+    fn current_rom_bank(cart: &Cartridge) -> u16 {
+        u16::from_le_bytes([cart.read(0x4000), cart.read(0x4001)])
+    }
+
+    #[test]
+    fn nine_bit_rom_banks() {
+        let mut cart = Cartridge::from_rom(test_rom(0x19, 0x08, 0x04, 0x4000)).unwrap();
+        assert_eq!(current_rom_bank(&cart), 1);
+        cart.write(0x2000, 0x23);
+        cart.write(0x3000, 0x01);
+        assert_eq!(current_rom_bank(&cart), 0x123);
+        cart.write(0x2000, 0x00);
+        assert_eq!(current_rom_bank(&cart), 0x100);
+        cart.write(0x3000, 0x00);
+        assert_eq!(current_rom_bank(&cart), 0);
+    }
+
+    #[test]
+    fn sixteen_ram_banks() {
+        let mut cart = Cartridge::from_rom(test_rom(0x1A, 0x08, 0x04, 0x4000)).unwrap();
+        cart.write(0x0000, 0x0A);
+        for bank in 0..16 {
+            cart.write(0x4000, bank);
+            cart.write(0xA000, bank);
+        }
+        for bank in 0..16 {
+            cart.write(0x4000, bank);
+            assert_eq!(cart.read(0xA000), bank);
+        }
+    }
+
+    #[test]
+    fn rumble() {
+        let mut cart = Cartridge::from_rom(test_rom(0x1D, 0x08, 0x04, 0x4000)).unwrap();
+        assert_eq!(cart.take_rumble(), None);
+        cart.write(0x4000, 0x09);
+        assert_eq!(cart.take_rumble(), Some(true));
+        assert_eq!(cart.take_rumble(), None);
+        cart.write(0x4000, 0x0A);
+        assert_eq!(cart.take_rumble(), None);
+        cart.write(0x4000, 0x02);
+        assert_eq!(cart.take_rumble(), Some(false));
+
+        // The motor bit does not select RAM banks.
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        cart.write(0x4000, 0x0A);
+        assert_eq!(cart.read(0xA000), 0x42);
+    }
+}
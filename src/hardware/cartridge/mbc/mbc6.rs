@@ -0,0 +1,175 @@
+//! MBC6 splits the switchable areas in two: 0x4000–0x5FFF and 0x6000–0x7FFF each map
+//! an 8 KiB bank of ROM or flash, and 0xA000–0xAFFF and 0xB000–0xBFFF a 4 KiB bank of RAM.
+//! Flash is kept after RAM, so it is saved along with it.
+//!
+//! See <https://gbdev.io/pandocs/MBC6.html>
+
+use crate::hardware::cartridge::{
+    flash::{self, Flash},
+    Hardware,
+};
+
+/// Size, in bytes, of each bank of ROM and flash.
+const ROM_BANK_SIZE: u32 = 8 * 1024;
+/// Size, in bytes, of each bank of RAM.
+const RAM_BANK_SIZE: u32 = 4 * 1024;
+
+#[derive(Default)]
+pub struct Mbc6 {
+    /// The bank mapped to each half of 0x4000–0x7FFF, and whether it is a bank of flash.
+    rom_banks: [u8; 2],
+    flash_mapped: [bool; 2],
+    /// The bank mapped to each half of 0xA000–0xBFFF.
+    ram_banks: [u8; 2],
+    flash_enabled: bool,
+    flash_write_enabled: bool,
+    flash: Flash,
+}
+
+impl Mbc6 {
+    /// Returns the absolute address of flash or ROM that an address of 0x4000–0x7FFF maps.
+    fn rom_addr(&self, addr: u16) -> (bool, u32) {
+        let window = (addr >= 0x6000) as usize;
+        let bank = self.rom_banks[window] as u32;
+        let flash = self.flash_mapped[window];
+        (flash, bank * ROM_BANK_SIZE + (addr as u32 & 0x1FFF))
+    }
+
+    /// Returns the absolute address of RAM that an address of 0xA000–0xBFFF maps,
+    /// if the bank exists. Banks past the end of RAM must not reach flash, kept after it.
+    fn ram_addr(&self, hw: &Hardware, addr: u16) -> Option<u32> {
+        let bank = self.ram_banks[(addr >= 0xB000) as usize] as u32;
+        let addr = bank * RAM_BANK_SIZE + (addr as u32 & 0x0FFF);
+        ((addr as usize) < hw.ram.data.len() - flash::SIZE).then_some(addr)
+    }
+}
+
+/// Returns the flash memory, kept at the end of RAM.
+fn flash_memory(hw: &Hardware) -> &[u8] {
+    &hw.ram.data[hw.ram.data.len() - flash::SIZE..]
+}
+
+pub fn read(hw: &Hardware, addr: u16, mbc6: &Mbc6) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
+        0x4000..=0x7FFF => match mbc6.rom_addr(addr) {
+            (true, addr) if mbc6.flash_enabled => flash_memory(hw)[addr as usize % flash::SIZE],
+            (true, _) => 0xFF,
+            (false, addr) => {
+                // Banks are half the usual size: the mask gains a bit.
+                let mask = (hw.rom.bank_mask() as u32) << 1 | 1;
+                let bank = (addr / ROM_BANK_SIZE) & mask;
+                hw.rom.at(bank * ROM_BANK_SIZE + addr % ROM_BANK_SIZE)
+            }
+        },
+        0xA000..=0xBFFF => mbc6
+            .ram_addr(hw, addr)
+            .map_or(0xFF, |addr| hw.ram.read(addr)),
+        _ => unreachable!(),
+    }
+}
+
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, mbc6: &mut Mbc6) {
+    match addr {
+        0x0000..=0x03FF => hw.ram.enabled = val & 0x0F == 0x0A,
+        0x0400..=0x07FF => mbc6.ram_banks[0] = val & 0b00000111,
+        0x0800..=0x0BFF => mbc6.ram_banks[1] = val & 0b00000111,
+        0x0C00..=0x0FFF => mbc6.flash_enabled = val & 1 != 0,
+        0x1000 => mbc6.flash_write_enabled = val & 1 != 0,
+        0x1001..=0x1FFF => (),
+        0x2000..=0x27FF => mbc6.rom_banks[0] = val & 0b01111111,
+        0x2800..=0x2FFF => mbc6.flash_mapped[0] = val == 0x08,
+        0x3000..=0x37FF => mbc6.rom_banks[1] = val & 0b01111111,
+        0x3800..=0x3FFF => mbc6.flash_mapped[1] = val == 0x08,
+        0x4000..=0x7FFF => {
+            let (true, flash_addr) = mbc6.rom_addr(addr) else {
+                return;
+            };
+            if !mbc6.flash_enabled {
+                return;
+            }
+            let start = hw.ram.data.len() - flash::SIZE;
+            let memory = &mut hw.ram.data[start..];
+            let addr = flash_addr % flash::SIZE as u32;
+            if mbc6
+                .flash
+                .write(memory, addr, val, mbc6.flash_write_enabled)
+            {
+                hw.ram.dirty = true;
+            }
+        }
+        0xA000..=0xBFFF => {
+            if let Some(addr) = mbc6.ram_addr(hw, addr) {
+                hw.ram.write(addr, val);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{cartridge::test_rom, Cartridge};
+
+    #[test]
+    fn rom_windows() {
+        let mut cart = Cartridge::from_rom(test_rom(0x20, 0x05, 0x03, 0x2000)).unwrap();
+        cart.write(0x2000, 0x05);
+        cart.write(0x3000, 0x7E);
+        assert_eq!(cart.read(0x4000), 0x05);
+        assert_eq!(cart.read(0x6000), 0x7E);
+        // Bank 0 can be mapped too.
+        cart.write(0x2000, 0x00);
+        assert_eq!(cart.read(0x4000), 0x00);
+    }
+
+    #[test]
+    fn ram_windows() {
+        let mut cart = Cartridge::from_rom(test_rom(0x20, 0x05, 0x03, 0x2000)).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0x0400, 0x03);
+        cart.write(0x0800, 0x04);
+        cart.write(0xA000, 0x33);
+        cart.write(0xB000, 0x44);
+        cart.write(0x0800, 0x03);
+        assert_eq!(cart.read(0xB000), 0x33);
+        assert_eq!(cart.read(0xA000), 0x33);
+    }
+
+    #[test]
+    fn missing_ram_spares_flash() {
+        let mut cart = Cartridge::from_rom(test_rom(0x20, 0x05, 0x00, 0x2000)).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0x0400, 0x01);
+        cart.write(0xA000, 0x42);
+        assert_eq!(cart.read(0xA000), 0xFF);
+
+        cart.write(0x0C00, 0x01);
+        cart.write(0x2000, 0x00);
+        cart.write(0x2800, 0x08);
+        assert_eq!(cart.read(0x5000), 0xFF);
+    }
+
+    #[test]
+    fn flash() {
+        let mut cart = Cartridge::from_rom(test_rom(0x20, 0x05, 0x03, 0x2000)).unwrap();
+        // Map flash banks 2 and 1, where the unlock addresses are.
+        cart.write(0x2000, 0x02);
+        cart.write(0x2800, 0x08);
+        cart.write(0x3000, 0x01);
+        cart.write(0x3800, 0x08);
+        assert_eq!(cart.read(0x4000), 0xFF);
+        cart.write(0x0C00, 0x01);
+        cart.write(0x1000, 0x01);
+        assert_eq!(cart.read(0x4000), 0xFF);
+
+        cart.write(0x5555, 0xAA);
+        cart.write(0x6AAA, 0x55);
+        cart.write(0x5555, 0xA0);
+        cart.write(0x4000, 0x42);
+        assert_eq!(cart.read(0x4000), 0x42);
+        // Map ROM again.
+        cart.write(0x2800, 0x00);
+        assert_eq!(cart.read(0x4000), 0x02);
+    }
+}
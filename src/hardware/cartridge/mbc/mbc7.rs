@@ -0,0 +1,136 @@
+//! MBC7 has no RAM: it maps a 2-axis accelerometer and a serial EEPROM, used for saves,
+//! to 0xA000–0xAFFF. Both are reached only after enabling them with two registers.
+//!
+//! See <https://gbdev.io/pandocs/MBC7.html>
+
+use crate::hardware::cartridge::{eeprom::Eeprom, Hardware};
+
+/// Value of an accelerometer axis when the console is held flat.
+const ACCEL_CENTER: f32 = 0x81D0 as f32;
+/// Change of an accelerometer axis for 1 g, that is a tilt of 90°.
+const ACCEL_PER_G: f32 = 0x70 as f32;
+/// Value of the latched accelerometer axes after erasing them.
+const ACCEL_ERASED: u16 = 0x8000;
+
+pub struct Mbc7 {
+    /// The two enable registers, at 0x0000–0x1FFF and 0x4000–0x5FFF.
+    enabled: bool,
+    enabled_2: bool,
+    /// Accelerometer axes, as last latched.
+    x: u16,
+    y: u16,
+    /// Whether the axes were erased, so that they can be latched again.
+    erased: bool,
+    eeprom: Eeprom,
+}
+
+impl Default for Mbc7 {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            enabled_2: false,
+            x: ACCEL_ERASED,
+            y: ACCEL_ERASED,
+            erased: false,
+            eeprom: Eeprom::new(),
+        }
+    }
+}
+
+pub fn read(hw: &Hardware, addr: u16, mbc7: &Mbc7) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => hw.rom.at(addr as u32),
+        0x4000..=0x7FFF => hw.rom.at_current_bank(addr - 0x4000),
+        0xA000..=0xAFFF if hw.ram.enabled => match addr & 0x00F0 {
+            0x20 => mbc7.x as u8,
+            0x30 => (mbc7.x >> 8) as u8,
+            0x40 => mbc7.y as u8,
+            0x50 => (mbc7.y >> 8) as u8,
+            0x60 => 0x00,
+            0x80 => mbc7.eeprom.read(),
+            _ => 0xFF,
+        },
+        0xA000..=0xBFFF => 0xFF,
+        _ => unreachable!(),
+    }
+}
+
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, mbc7: &mut Mbc7) {
+    match addr {
+        0x0000..=0x1FFF => mbc7.enabled = val == 0x0A,
+        0x2000..=0x3FFF => hw.rom.set_bank(val),
+        0x4000..=0x5FFF => mbc7.enabled_2 = val == 0x40,
+        0x6000..=0x7FFF => (), // Not connected to the controller.
+        0xA000..=0xAFFF if hw.ram.enabled => match addr & 0x00F0 {
+            0x00 if val == 0x55 => {
+                mbc7.x = ACCEL_ERASED;
+                mbc7.y = ACCEL_ERASED;
+                mbc7.erased = true;
+            }
+            0x10 if val == 0xAA && mbc7.erased => {
+                let (x, y) = hw.tilt;
+                // Casting saturates, should the tilt go beyond what the sensor measures.
+                mbc7.x = (ACCEL_CENTER + ACCEL_PER_G * x) as u16;
+                mbc7.y = (ACCEL_CENTER + ACCEL_PER_G * y) as u16;
+                mbc7.erased = false;
+            }
+            0x80 => mbc7.eeprom.write(&mut hw.ram, val),
+            _ => (),
+        },
+        0xA000..=0xBFFF => (),
+        _ => unreachable!(),
+    }
+    // The EEPROM is kept in RAM, which makes saving it follow RAM being disabled.
+    hw.ram.enabled = mbc7.enabled && mbc7.enabled_2;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{cartridge::test_rom, Cartridge};
+
+    /// Builds a cartridge with both RAM enables set.
+    fn cartridge() -> Cartridge {
+        let mut cart = Cartridge::from_rom(test_rom(0x22, 0x01, 0x00, 0x4000)).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0x4000, 0x40);
+        cart
+    }
+
+    fn axes(cart: &Cartridge) -> (u16, u16) {
+        (
+            u16::from_le_bytes([cart.read(0xA020), cart.read(0xA030)]),
+            u16::from_le_bytes([cart.read(0xA040), cart.read(0xA050)]),
+        )
+    }
+
+    #[test]
+    fn accelerometer() {
+        let mut cart = cartridge();
+        cart.set_tilt(1.0, -0.5);
+
+        // Latching needs erasing first.
+        cart.write(0xA010, 0xAA);
+        assert_eq!(axes(&cart), (0x8000, 0x8000));
+        cart.write(0xA000, 0x55);
+        cart.write(0xA010, 0xAA);
+        assert_eq!(axes(&cart), (0x81D0 + 0x70, 0x81D0 - 0x38));
+
+        // The axes stay latched.
+        cart.set_tilt(0.0, 0.0);
+        cart.write(0xA010, 0xAA);
+        assert_eq!(axes(&cart), (0x81D0 + 0x70, 0x81D0 - 0x38));
+        cart.write(0xA000, 0x55);
+        assert_eq!(axes(&cart), (0x8000, 0x8000));
+    }
+
+    #[test]
+    fn both_enables_needed() {
+        let mut cart = cartridge();
+        cart.write(0x4000, 0x00);
+        assert_eq!(cart.read(0xA060), 0xFF);
+        cart.write(0x4000, 0x40);
+        assert_eq!(cart.read(0xA060), 0x00);
+        assert_eq!(cart.read(0xA080) & 1, 1);
+        assert_eq!(cart.read(0xB060), 0xFF);
+    }
+}
@@ -0,0 +1,157 @@
+//! MMM01 drives multi-game compilations. It boots into a menu at the end of the ROM,
+//! which selects a game with the bank registers and then maps it: from then on,
+//! the outer bank bits, and the ROM and RAM bank bits the menu masked, are locked,
+//! so the game banks as on an MBC1 of its own. MBC1 banking mode and multiplexing
+//! are not emulated.
+//!
+//! See <https://gbdev.io/pandocs/MMM01.html>
+
+use crate::hardware::cartridge::Hardware;
+
+/// The bank registers, as last written.
+#[derive(Default)]
+pub struct Banks {
+    /// Whether a game was mapped, which locks most registers.
+    mapped: bool,
+    /// The lower 5 bits of the ROM bank, and which of them are locked once mapped.
+    rom_lower: u8,
+    rom_mask: u8,
+    /// Bits 5–6 and 7–8 of the ROM bank.
+    rom_middle: u8,
+    rom_upper: u8,
+    /// The lower 2 bits of the RAM bank, and which of them are locked once mapped.
+    ram_lower: u8,
+    ram_mask: u8,
+    /// Bits 2–3 of the RAM bank.
+    ram_upper: u8,
+}
+
+impl Banks {
+    /// Returns the bits of the ROM bank that the game cannot change.
+    fn rom_outer(&self) -> u16 {
+        (self.rom_upper as u16) << 7 | (self.rom_middle as u16) << 5
+    }
+
+    /// Returns the ROM bank mapped at 0x0000–0x3FFF. Until a game is mapped,
+    /// the last 32 KiB of the ROM, where the menu is, are mapped.
+    fn zero_bank(&self) -> u16 {
+        if !self.mapped {
+            return 0x1FE;
+        }
+        self.rom_outer() | (self.rom_lower & self.rom_mask) as u16
+    }
+
+    /// Returns the ROM bank mapped at 0x4000–0x7FFF.
+    fn rom_bank(&self) -> u16 {
+        if !self.mapped {
+            return 0x1FF;
+        }
+        // As on MBC1, bank 0 of the game is translated to 1.
+        let lower = if self.rom_lower & !self.rom_mask == 0 {
+            self.rom_lower | 1
+        } else {
+            self.rom_lower
+        };
+        self.rom_outer() | lower as u16
+    }
+
+    /// Writes a register whose bits in `mask` are locked once a game is mapped.
+    fn write_masked(&self, reg: u8, val: u8, mask: u8) -> u8 {
+        if self.mapped {
+            (reg & mask) | (val & !mask)
+        } else {
+            val
+        }
+    }
+
+    /// Maps the RAM bank the registers select.
+    fn apply(&self, hw: &mut Hardware) {
+        hw.ram
+            .set_current_bank_wide(self.ram_upper << 2 | self.ram_lower);
+    }
+}
+
+pub fn read(hw: &Hardware, addr: u16, banks: &Banks) -> u8 {
+    match addr {
+        0x0000..=0x3FFF => hw.rom.at_bank(banks.zero_bank(), addr),
+        0x4000..=0x7FFF => hw.rom.at_bank(banks.rom_bank(), addr - 0x4000),
+        0xA000..=0xBFFF => hw.ram.read_current_bank(addr - 0xA000),
+        _ => unreachable!(),
+    }
+}
+
+pub fn write(hw: &mut Hardware, addr: u16, val: u8, banks: &mut Banks) {
+    match addr {
+        0x0000..=0x1FFF => {
+            hw.ram.enabled = val & 0x0F == 0x0A;
+            if !banks.mapped {
+                banks.ram_mask = (val >> 4) & 0b11;
+                banks.mapped = val & 0b01000000 != 0;
+            }
+        }
+        0x2000..=0x3FFF => {
+            banks.rom_lower = banks.write_masked(banks.rom_lower, val & 0b00011111, banks.rom_mask);
+            if !banks.mapped {
+                banks.rom_middle = (val >> 5) & 0b11;
+            }
+        }
+        0x4000..=0x5FFF => {
+            banks.ram_lower = banks.write_masked(banks.ram_lower, val & 0b11, banks.ram_mask);
+            if !banks.mapped {
+                banks.ram_upper = (val >> 2) & 0b11;
+                banks.rom_upper = (val >> 4) & 0b11;
+            }
+        }
+        0x6000..=0x7FFF => {
+            if !banks.mapped {
+                // The mask covers bits 1–4 of the lower ROM bank.
+                banks.rom_mask = (val >> 1) & 0b00011110;
+            }
+        }
+        0xA000..=0xBFFF => hw.ram.write_current_bank(addr - 0xA000, val),
+        _ => unreachable!(),
+    }
+    banks.apply(hw);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hardware::{
+        cartridge::{header::LOGO, test_rom},
+        Cartridge,
+    };
+
+    /// Builds a 512 KiB compilation whose banks start with their own number,
+    /// with the header of the menu in the last 32 KiB.
+    fn cartridge() -> Cartridge {
+        let mut rom = test_rom(0x01, 0x04, 0x00, 0x4000);
+        let menu = &mut rom[30 * 0x4000..];
+        menu[0x104..0x134].copy_from_slice(&LOGO);
+        menu[0x147] = 0x0B;
+        menu[0x148] = 0x04;
+        Cartridge::from_rom(rom).unwrap()
+    }
+
+    #[test]
+    fn menu_then_game() {
+        let mut cart = cartridge();
+        assert_eq!((cart.read(0x0000), cart.read(0x4000)), (30, 31));
+
+        // The menu picks the game at bank 8, locks bits 3–4, then maps it.
+        cart.write(0x2000, 0x08);
+        cart.write(0x6000, 0x30);
+        assert_eq!((cart.read(0x0000), cart.read(0x4000)), (30, 31));
+        cart.write(0x0000, 0x40);
+        assert_eq!((cart.read(0x0000), cart.read(0x4000)), (8, 9));
+
+        // The game banks within its 8 banks.
+        cart.write(0x2000, 0x03);
+        assert_eq!(cart.read(0x4000), 11);
+        cart.write(0x2000, 0x00);
+        assert_eq!(cart.read(0x4000), 9);
+        cart.write(0x6000, 0x00);
+        cart.write(0x2000, 0x12);
+        assert_eq!(cart.read(0x4000), 10);
+        assert_eq!(cart.read(0x0000), 8);
+    }
+}
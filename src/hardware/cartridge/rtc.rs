@@ -0,0 +1,358 @@
+//! The `rtc` module emulates the real-time clock of MBC3 cartridges.
+//!
+//! The clock counts seconds, minutes, hours and up to 511 days, while the game reads a copy
+//! latched on request, so that it does not change in the middle of reading it.
+//! Its state is saved in the footer most emulators append to `.sav` files.
+//!
+//! See <https://gbdev.io/pandocs/MBC3.html#the-clock-counter-registers>
+//! and <https://bgb.bircd.org/rtcsave.html>
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::hardware::MASTER_CLOCK;
+
+/// Size, in bytes, of the footer appended to saves: the registers, the latched registers,
+/// each taking 4 bytes, and a 64-bit UNIX timestamp, all in little endian.
+pub const FOOTER_SIZE: usize = 48;
+/// Size of the footer written by older emulators, whose timestamp is only 32 bits.
+const SHORT_FOOTER_SIZE: usize = 44;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub struct Rtc {
+    /// The registers that keep counting.
+    registers: RtcRegisters,
+    /// A copy of the registers, which is what the game reads.
+    latched: RtcRegisters,
+    curr_register: u8,
+    /// Whether 0 was just written to the latch register, so that writing 1 latches.
+    latch_armed: bool,
+    pub enabled: bool,
+    /// Clock ticks elapsed since the last second.
+    ticks: u32,
+    /// Whether the clock keeps counting while the emulator is not running,
+    /// following the time of the host between a save and the next load.
+    pub follow_host: bool,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Self {
+            registers: Default::default(),
+            latched: Default::default(),
+            curr_register: 0,
+            latch_armed: false,
+            enabled: true,
+            ticks: 0,
+            follow_host: false,
+        }
+    }
+
+    /// Reads a latched register.
+    pub fn read_register(&self, idx: usize) -> u8 {
+        self.latched.read(idx)
+    }
+
+    /// Writes a register. The latched copy is written too, so the game reads back its value.
+    pub fn write_register(&mut self, idx: usize, val: u8) {
+        // Writing the seconds restarts the current second.
+        if idx == 0 {
+            self.ticks = 0;
+        }
+        self.registers.write(idx, val);
+        self.latched.write(idx, val);
+    }
+
+    pub fn read_current_register(&self) -> u8 {
+        if !self.enabled {
+            return 0xFF;
+        }
+        self.read_register(self.curr_register.into())
+    }
+
+    pub fn write_current_register(&mut self, val: u8) {
+        if !self.enabled {
+            return;
+        }
+        self.write_register(self.curr_register.into(), val)
+    }
+
+    pub fn set_current_register(&mut self, reg: u8) {
+        self.curr_register = reg;
+    }
+
+    /// Writes the latch register. Writing 0 and then 1 copies the registers
+    /// to their latched counterparts.
+    pub fn write_latch(&mut self, val: u8) {
+        if self.latch_armed && val == 1 {
+            self.latched = self.registers;
+        }
+        self.latch_armed = val == 0;
+    }
+
+    /// Advances the clock by a number of emulated clock ticks.
+    pub fn tick(&mut self, ticks: u8) {
+        if self.registers.bools.halted() {
+            return;
+        }
+        self.ticks += ticks as u32;
+        if self.ticks >= MASTER_CLOCK {
+            self.ticks -= MASTER_CLOCK;
+            self.registers.advance_second();
+        }
+    }
+
+    /// Advances the clock by a number of seconds, unless it is halted.
+    fn advance(&mut self, mut seconds: u64) {
+        if self.registers.bools.halted() {
+            return;
+        }
+        while seconds > 0 {
+            // Skipping whole days keeps the time of day, as long as it is a valid one.
+            if seconds >= SECONDS_PER_DAY && self.registers.time_valid() {
+                self.registers.advance_day();
+                seconds -= SECONDS_PER_DAY;
+            } else {
+                self.registers.advance_second();
+                seconds -= 1;
+            }
+        }
+    }
+
+    /// Returns the footer to append to saves.
+    pub fn footer(&self) -> [u8; FOOTER_SIZE] {
+        let mut footer = [0; FOOTER_SIZE];
+        let registers = (0..5)
+            .map(|idx| self.registers.read(idx))
+            .chain((0..5).map(|idx| self.latched.read(idx)));
+        for (bytes, reg) in footer.chunks_exact_mut(4).zip(registers) {
+            bytes.copy_from_slice(&(reg as u32).to_le_bytes());
+        }
+        footer[40..].copy_from_slice(&unix_time().to_le_bytes());
+        footer
+    }
+
+    /// Restores the state from the footer of a save, and returns whether it was valid.
+    /// If following the host, the time passed since the save is added.
+    pub fn restore(&mut self, footer: &[u8]) -> bool {
+        let timestamp = match footer.len() {
+            FOOTER_SIZE => u64::from_le_bytes(footer[40..48].try_into().unwrap()),
+            SHORT_FOOTER_SIZE => u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64,
+            _ => return false,
+        };
+        for (idx, bytes) in footer[..40].chunks_exact(4).enumerate() {
+            // Registers are 8 bits wide: the upper bytes are always 0.
+            match idx {
+                0..=4 => self.registers.write(idx, bytes[0]),
+                _ => self.latched.write(idx - 5, bytes[0]),
+            }
+        }
+        self.ticks = 0;
+        if self.follow_host {
+            self.advance(unix_time().saturating_sub(timestamp));
+        }
+        true
+    }
+}
+
+/// Returns the seconds elapsed since the UNIX epoch, according to the host.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+#[derive(Clone, Copy, Default)]
+struct RtcRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// Lower bits of the day counter.
+    /// The most significant bit is contained in [`Self::bools`].
+    days_lower: u8,
+    bools: RtcBoolRegisters,
+}
+
+impl RtcRegisters {
+    fn read(&self, idx: usize) -> u8 {
+        match idx {
+            0 => self.seconds,
+            1 => self.minutes,
+            2 => self.hours,
+            3 => self.days_lower,
+            4 => self.bools.0.into_value(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Writes a register. Bits that are not backed by the counters are dropped.
+    fn write(&mut self, idx: usize, val: u8) {
+        match idx {
+            0 => self.seconds = val & 0b00111111,
+            1 => self.minutes = val & 0b00111111,
+            2 => self.hours = val & 0b00011111,
+            3 => self.days_lower = val,
+            4 => self.bools = RtcBoolRegisters(bitmaps::Bitmap::from_value(val & 0b11000001)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns whether the time of day is one a clock can show.
+    /// Games can write any value that fits the registers, such as 63 seconds.
+    fn time_valid(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    /// Counts one second. A counter holding an invalid value keeps counting
+    /// until it overflows its bits, without carrying to the next one.
+    fn advance_second(&mut self) {
+        if self.seconds != 59 {
+            self.seconds = (self.seconds + 1) & 0b00111111;
+            return;
+        }
+        self.seconds = 0;
+        if self.minutes != 59 {
+            self.minutes = (self.minutes + 1) & 0b00111111;
+            return;
+        }
+        self.minutes = 0;
+        if self.hours != 23 {
+            self.hours = (self.hours + 1) & 0b00011111;
+            return;
+        }
+        self.hours = 0;
+        self.advance_day();
+    }
+
+    /// Counts one day. Past day 511, the counter starts over and the carry bit is set,
+    /// until the game clears it.
+    fn advance_day(&mut self) {
+        let days = ((self.bools.days_upper() as u16) << 8 | self.days_lower as u16) + 1;
+        if days > 0x1FF {
+            self.bools.set_days_overflowed(true);
+        }
+        self.days_lower = days as u8;
+        self.bools.set_days_upper(days & 0x100 != 0);
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct RtcBoolRegisters(bitmaps::Bitmap<8>);
+
+impl RtcBoolRegisters {
+    fn days_upper(&self) -> bool {
+        self.0.get(0)
+    }
+
+    /// Whether the clock is stopped.
+    fn halted(&self) -> bool {
+        self.0.get(6)
+    }
+
+    fn set_days_upper(&mut self, val: bool) {
+        self.0.set(0, val);
+    }
+
+    fn set_days_overflowed(&mut self, val: bool) {
+        self.0.set(7, val);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latched(rtc: &mut Rtc) -> [u8; 5] {
+        rtc.write_latch(0);
+        rtc.write_latch(1);
+        [0, 1, 2, 3, 4].map(|idx| rtc.read_register(idx))
+    }
+
+    #[test]
+    fn latch_needs_zero_then_one() {
+        let mut rtc = Rtc::new();
+        rtc.advance(5);
+        rtc.write_latch(1);
+        assert_eq!(rtc.read_register(0), 0);
+        rtc.write_latch(0);
+        rtc.write_latch(1);
+        assert_eq!(rtc.read_register(0), 5);
+
+        // The latched copy does not move until latched again.
+        rtc.advance(5);
+        assert_eq!(rtc.read_register(0), 5);
+        assert_eq!(latched(&mut rtc)[0], 10);
+    }
+
+    #[test]
+    fn ticks_count_seconds() {
+        let mut rtc = Rtc::new();
+        for _ in 0..MASTER_CLOCK / 4 {
+            rtc.tick(4);
+        }
+        assert_eq!(latched(&mut rtc)[0], 1);
+    }
+
+    #[test]
+    fn carries() {
+        let mut rtc = Rtc::new();
+        for (idx, val) in [59, 59, 23, 0xFF, 0x01].into_iter().enumerate() {
+            rtc.write_register(idx, val);
+        }
+        rtc.advance(1);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, 0x80]);
+
+        // Invalid values overflow without carrying.
+        rtc.write_register(0, 63);
+        rtc.advance(1);
+        assert_eq!(latched(&mut rtc)[..2], [0, 0]);
+
+        // Whole days keep the time of day.
+        rtc.write_register(2, 12);
+        rtc.advance(3 * SECONDS_PER_DAY);
+        assert_eq!(latched(&mut rtc), [0, 0, 12, 3, 0x80]);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let mut rtc = Rtc::new();
+        rtc.write_register(4, 0x40);
+        rtc.advance(100);
+        rtc.tick(4);
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, 0x40]);
+    }
+
+    #[test]
+    fn footer_round_trip() {
+        let mut rtc = Rtc::new();
+        for (idx, val) in [1, 2, 3, 4, 0x01].into_iter().enumerate() {
+            rtc.write_register(idx, val);
+        }
+        rtc.advance(1);
+        let footer = rtc.footer();
+        assert_eq!(footer[..8], [2, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(footer[20..24], [1, 0, 0, 0]);
+
+        let mut restored = Rtc::new();
+        assert!(restored.restore(&footer));
+        assert_eq!(restored.read_register(0), 1);
+        assert_eq!(latched(&mut restored), [2, 2, 3, 4, 0x01]);
+        assert!(!restored.restore(&footer[..40]));
+    }
+
+    #[test]
+    fn follow_host_across_sessions() {
+        let mut rtc = Rtc::new();
+        let mut footer = rtc.footer();
+        let an_hour_ago = unix_time() - 60 * 60;
+        footer[40..].copy_from_slice(&an_hour_ago.to_le_bytes());
+
+        let mut emulated = Rtc::new();
+        emulated.restore(&footer);
+        assert_eq!(latched(&mut emulated)[2], 0);
+
+        rtc.follow_host = true;
+        rtc.restore(&footer);
+        assert_eq!(latched(&mut rtc)[2], 1);
+    }
+}
@@ -0,0 +1,110 @@
+//! The `save` module persists the state of battery-backed cartridges.
+//!
+//! Saves use the raw layout most emulators share: the contents of cartridge RAM, bank after bank,
+//! with nothing before them. Where they are kept is up to the embedder, through [`SaveStorage`].
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Somewhere to keep the save of a cartridge, such as a file or a browser's local storage.
+pub trait SaveStorage {
+    /// Returns the last save stored, or `None` if there is none yet.
+    fn load(&mut self) -> io::Result<Option<Vec<u8>>>;
+    /// Replaces the stored save with `data`.
+    fn store(&mut self, data: &[u8]) -> io::Result<()>;
+}
+
+/// Keeps the save in a file, usually a `.sav` file beside the ROM.
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Keeps the save beside a ROM, with the same name and the `.sav` extension,
+    /// as other emulators do.
+    pub fn beside_rom(rom: impl AsRef<Path>) -> Self {
+        Self::new(rom.as_ref().with_extension("sav"))
+    }
+}
+
+impl SaveStorage for FileStorage {
+    fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn store(&mut self, data: &[u8]) -> io::Result<()> {
+        // Write a copy first, so that a crash halfway through does not corrupt the last save.
+        let tmp = self.path.with_extension("sav.tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::hardware::{cartridge::test_rom, Cartridge};
+
+    /// Keeps the save in memory, shared with the test so it can be inspected.
+    #[derive(Clone, Default)]
+    pub struct MemoryStorage(pub Rc<RefCell<Option<Vec<u8>>>>);
+
+    impl SaveStorage for MemoryStorage {
+        fn load(&mut self) -> io::Result<Option<Vec<u8>>> {
+            Ok(self.0.borrow().clone())
+        }
+
+        fn store(&mut self, data: &[u8]) -> io::Result<()> {
+            *self.0.borrow_mut() = Some(data.to_vec());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn load_and_save() {
+        let storage = MemoryStorage::default();
+        let mut save = vec![0; 0x2000];
+        save[0x10] = 0x42;
+        *storage.0.borrow_mut() = Some(save);
+
+        let mut cart = Cartridge::from_rom(test_rom(0x03, 0x00, 0x02, 0x4000)).unwrap();
+        cart.set_storage(Box::new(storage.clone())).unwrap();
+        cart.write(0x0000, 0x0A);
+        assert_eq!(cart.read(0xA010), 0x42);
+
+        cart.write(0xA011, 0x43);
+        assert!(!cart.save_requested());
+        // Games disable RAM once they are done writing it, which is a good time to save.
+        cart.write(0x0000, 0x00);
+        assert!(cart.save_requested());
+        cart.save().unwrap();
+        assert!(!cart.save_requested());
+
+        let saved = storage.0.borrow().clone().unwrap();
+        assert_eq!(saved.len(), 0x2000);
+        assert_eq!(saved[0x10..0x12], [0x42, 0x43]);
+    }
+
+    #[test]
+    fn nothing_saved_without_battery() {
+        let storage = MemoryStorage::default();
+        let mut cart = Cartridge::from_rom(test_rom(0x02, 0x00, 0x02, 0x4000)).unwrap();
+        cart.set_storage(Box::new(storage.clone())).unwrap();
+        cart.write(0x0000, 0x0A);
+        cart.write(0xA000, 0x42);
+        cart.save().unwrap();
+        assert_eq!(*storage.0.borrow(), None);
+    }
+}
@@ -0,0 +1,166 @@
+//! The `sensor` module emulates the image sensor of the Pocket Camera, along with
+//! the processing it does before the image reaches cartridge RAM.
+//!
+//! A scene, given by an [`ImageSource`], is exposed, edge enhanced, then dithered
+//! to the 4 shades of the Game Boy with a 4×4 matrix of thresholds, and finally
+//! laid out as tiles. The analog gain and voltage references are not emulated.
+//!
+//! See <https://gbdev.io/pandocs/Gameboy_Camera.html>
+
+/// Size, in pixels, of the images stored in RAM.
+pub const WIDTH: usize = 128;
+pub const HEIGHT: usize = 112;
+
+/// Brightness of each pixel of a scene, row by row, from 0 (black) to 255 (white).
+pub type Frame = [[u8; WIDTH]; HEIGHT];
+
+/// Size, in bytes, of an image laid out as 2-bit tiles.
+pub const IMAGE_SIZE: usize = WIDTH * HEIGHT / 4;
+
+/// What the camera sees. It could be a webcam, a still image or a pattern.
+pub trait ImageSource {
+    /// Returns the scene in front of the camera, when a picture is taken.
+    fn capture(&mut self) -> Frame;
+}
+
+/// The exposure time at which brightness is left unchanged.
+const NEUTRAL_EXPOSURE: u32 = 0x0800;
+/// Edge enhancement ratios, in quarters, selected by register 4.
+const EDGE_RATIOS: [i32; 8] = [2, 3, 4, 5, 8, 12, 16, 20];
+
+/// The registers of the sensor that shape the image, as the controller maps them.
+pub struct Settings<'a> {
+    pub exposure: u16,
+    /// Whether to enhance edges along rows and columns.
+    pub horizontal: bool,
+    pub vertical: bool,
+    /// Index in [`EDGE_RATIOS`].
+    pub edge_ratio: u8,
+    pub invert: bool,
+    /// 3 increasing thresholds for each cell of the 4×4 dithering matrix.
+    pub matrix: &'a [u8],
+}
+
+/// Takes a picture of a scene, and returns it laid out as tiles, 16 per row.
+pub fn process(scene: &Frame, settings: &Settings) -> [u8; IMAGE_SIZE] {
+    let exposed = scene.map(|row| {
+        row.map(|pixel| {
+            (pixel as u32 * settings.exposure as u32 / NEUTRAL_EXPOSURE).min(255) as i32
+        })
+    });
+
+    let ratio = EDGE_RATIOS[settings.edge_ratio as usize];
+    let mut image = [0; IMAGE_SIZE];
+    for (y, row) in exposed.iter().enumerate() {
+        for (x, &pixel) in row.iter().enumerate() {
+            // Pixels past the border repeat the border.
+            let at = |dx: isize, dy: isize| {
+                let y = y.saturating_add_signed(dy).min(HEIGHT - 1);
+                let x = x.saturating_add_signed(dx).min(WIDTH - 1);
+                exposed[y][x]
+            };
+            let mut edge = 0;
+            if settings.horizontal {
+                edge += 2 * pixel - at(-1, 0) - at(1, 0);
+            }
+            if settings.vertical {
+                edge += 2 * pixel - at(0, -1) - at(0, 1);
+            }
+            let mut pixel = (pixel + edge * ratio / 4).clamp(0, 255) as u8;
+            if settings.invert {
+                pixel = 255 - pixel;
+            }
+
+            let cell = 3 * ((y % 4) * 4 + x % 4);
+            let thresholds = &settings.matrix[cell..cell + 3];
+            let shade = 3 - thresholds.iter().filter(|&&t| pixel >= t).count() as u8;
+
+            let tile = (y / 8) * (WIDTH / 8) + x / 8;
+            let offset = tile * 16 + (y % 8) * 2;
+            let bit = 7 - x % 8;
+            image[offset] |= (shade & 1) << bit;
+            image[offset + 1] |= (shade >> 1) << bit;
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Vertical stripes of increasing brightness, 32 pixels wide.
+    pub struct Stripes;
+
+    impl ImageSource for Stripes {
+        fn capture(&mut self) -> Frame {
+            [std::array::from_fn(|x| (x / 32 * 85) as u8); HEIGHT]
+        }
+    }
+
+    /// Returns the shade of a pixel of an image laid out as tiles.
+    pub fn shade(image: &[u8], x: usize, y: usize) -> u8 {
+        let offset = ((y / 8) * (WIDTH / 8) + x / 8) * 16 + (y % 8) * 2;
+        let bit = 7 - x % 8;
+        (image[offset] >> bit & 1) | (image[offset + 1] >> bit & 1) << 1
+    }
+
+    fn settings(matrix: &[u8]) -> Settings<'_> {
+        Settings {
+            exposure: NEUTRAL_EXPOSURE as u16,
+            horizontal: false,
+            vertical: false,
+            edge_ratio: 0,
+            invert: false,
+            matrix,
+        }
+    }
+
+    #[test]
+    fn dithering() {
+        let matrix = [0x40, 0x80, 0xC0].repeat(16);
+        let scene = Stripes.capture();
+        let image = process(&scene, &settings(&matrix));
+        let shades: Vec<u8> = (0..4)
+            .map(|stripe| shade(&image, stripe * 32, 50))
+            .collect();
+        assert_eq!(shades, [3, 2, 1, 0]);
+
+        let image = process(
+            &scene,
+            &Settings {
+                invert: true,
+                ..settings(&matrix)
+            },
+        );
+        assert_eq!(shade(&image, 0, 0), 0);
+
+        // Half the exposure darkens everything by a shade or so.
+        let image = process(
+            &scene,
+            &Settings {
+                exposure: NEUTRAL_EXPOSURE as u16 / 2,
+                ..settings(&matrix)
+            },
+        );
+        assert_eq!(shade(&image, 96, 0), 2);
+    }
+
+    #[test]
+    fn edges() {
+        let matrix = [0x40, 0x80, 0xC0].repeat(16);
+        let scene = Stripes.capture();
+        let image = process(
+            &scene,
+            &Settings {
+                horizontal: true,
+                edge_ratio: 2,
+                ..settings(&matrix)
+            },
+        );
+        // Each side of a boundary is pushed away from the other.
+        assert_eq!(shade(&image, 31, 0), 3);
+        assert_eq!(shade(&image, 32, 0), 1);
+        assert_eq!(shade(&image, 40, 0), 2);
+    }
+}
@@ -1,15 +1,37 @@
 mod cbprefix;
+pub mod disasm;
 mod instructions;
+#[cfg(test)]
+mod tests;
 
-use std::ops;
+use std::{io::Write, ops};
 
-use crate::hardware::Hardware;
+use crate::hardware::{Bus, Model, M_CYCLE};
 
 pub struct Cpu {
     regs: Registers,
 
+    /// Whether the CPU is idling until an interrupt is pending.
     halted: bool,
+    /// Whether the CPU is in low-power mode until a button is pressed.
+    stopped: bool,
+    /// The address of the illegal opcode that hung the CPU, if any. Only a reset recovers from it.
+    locked_up: Option<u16>,
+    /// Whether the next opcode fetch must not increment the program counter.
+    /// This happens when HALT is executed with IME unset and an interrupt already pending.
+    halt_bug: bool,
+    /// The Interrupt Master Enable flag (IME).
+    /// When false, no interrupt is serviced regardless of the IE register.
     interrupt_enabled: bool,
+    /// Whether the EI instruction was just executed.
+    /// EI enables interrupts only after the instruction following it.
+    enabling_interrupts: bool,
+
+    /// Clock ticks elapsed since the current instruction started.
+    cycles: u8,
+
+    /// Where to log executed instructions, if anywhere.
+    trace: Option<Box<dyn Write>>,
 }
 
 impl Cpu {
@@ -17,28 +39,187 @@ impl Cpu {
         Self {
             regs: Default::default(),
             halted: false,
-            interrupt_enabled: true,
+            stopped: false,
+            locked_up: None,
+            halt_bug: false,
+            interrupt_enabled: false,
+            enabling_interrupts: false,
+            cycles: 0,
+            trace: None,
+        }
+    }
+
+    /// Executes one instruction, or services one interrupt, and returns how many clock ticks it took.
+    /// Peripherals are advanced by one M-cycle at every memory access.
+    pub fn tick<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.cycles = 0;
+        if self.locked_up.is_some() {
+            // Interrupts are not serviced anymore, but the rest of the system keeps running.
+            self.idle(bus);
+            return self.cycles;
+        }
+        if self.stopped {
+            if !bus.joypad_pressed() {
+                // The system clock is halted, so peripherals are not ticked.
+                return M_CYCLE;
+            }
+            self.stopped = false;
+        }
+        if self.halted {
+            // HALT is exited as soon as an interrupt is pending, even if IME is unset.
+            if bus.pending_interrupt().is_none() {
+                self.idle(bus);
+                return self.cycles;
+            }
+            self.halted = false;
+        }
+        if self.interrupt_enabled && bus.pending_interrupt().is_some() {
+            self.service_interrupt(bus);
+            return self.cycles;
+        }
+        if self.enabling_interrupts {
+            self.enabling_interrupts = false;
+            self.interrupt_enabled = true;
+        }
+        if self.trace.is_some() {
+            self.trace_instruction(bus);
+        }
+        let opcode = self.pop_prog_counter(bus);
+        instructions::execute(self, bus, opcode);
+        self.cycles
+    }
+
+    /// Puts the CPU back in its power-on state: registers are zeroed,
+    /// so execution starts at 0x0000. Tracing is kept.
+    pub fn reset(&mut self) {
+        *self = Self {
+            trace: self.trace.take(),
+            ..Self::new()
+        };
+    }
+
+    /// Returns the address of the illegal opcode that hung the CPU, if it is hung.
+    pub fn locked_up(&self) -> Option<u16> {
+        self.locked_up
+    }
+
+    pub fn regs(&self) -> &Registers {
+        &self.regs
+    }
+
+    /// Gives access to registers, for debuggers to inspect and alter the program state.
+    pub fn regs_mut(&mut self) -> &mut Registers {
+        &mut self.regs
+    }
+
+    /// Sets where to log every executed instruction, or disables logging if `None`.
+    /// Lines are written in the format expected by gameboy-doctor:
+    /// registers and the 4 bytes at the program counter, before the instruction executes.
+    ///
+    /// See <https://github.com/robert/gameboy-doctor>
+    pub fn set_trace(&mut self, sink: Option<Box<dyn Write>>) {
+        self.trace = sink;
+    }
+
+    fn trace_instruction<B: Bus>(&mut self, bus: &B) {
+        let Some(sink) = self.trace.as_mut() else {
+            return;
+        };
+        let regs = &self.regs;
+        let pc = regs.prog_counter;
+        let pcmem = [0, 1, 2, 3].map(|i| bus.peek(pc.wrapping_add(i)));
+        let line = writeln!(
+            sink,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            regs.a,
+            u8::from(regs.flags),
+            regs.b,
+            regs.c,
+            regs.d,
+            regs.e,
+            regs.h,
+            regs.l,
+            regs.stack_pointer,
+            pc,
+            pcmem[0],
+            pcmem[1],
+            pcmem[2],
+            pcmem[3],
+        );
+        if line.is_err() {
+            // A broken sink would fail on every instruction: stop tracing instead.
+            self.trace = None;
         }
     }
 
-    pub fn tick(&mut self, hw: &mut Hardware) -> u8 {
-        let opcode = self.pop_prog_counter(hw);
-        instructions::execute(self, hw, opcode)
+    /// Pushes the program counter onto the stack and jumps to the vector of the pending interrupt.
+    fn service_interrupt<B: Bus>(&mut self, bus: &mut B) {
+        self.interrupt_enabled = false;
+        self.idle(bus);
+        self.idle(bus);
+
+        let [msb, lsb] = self.regs.prog_counter.to_be_bytes();
+        self.regs.stack_pointer = self.regs.stack_pointer.wrapping_sub(1);
+        self.write(bus, self.regs.stack_pointer, msb);
+        // The interrupt is chosen only after pushing the most significant byte.
+        // If that push overwrote IE, the dispatch is cancelled and execution resumes at 0x0000.
+        let vector = match bus.pending_interrupt() {
+            Some(int) => {
+                bus.acknowledge_interrupt(int);
+                int.vector()
+            }
+            None => 0x0000,
+        };
+        self.regs.stack_pointer = self.regs.stack_pointer.wrapping_sub(1);
+        self.write(bus, self.regs.stack_pointer, lsb);
+        self.regs.prog_counter = vector;
+        self.idle(bus);
     }
 
-    fn pop_prog_counter(&mut self, hw: &mut Hardware) -> u8 {
-        let mem = hw.read(self.regs.prog_counter);
-        self.regs.prog_counter += 1;
+    fn pop_prog_counter<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let mem = self.read(bus, self.regs.prog_counter);
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.regs.prog_counter = self.regs.prog_counter.wrapping_add(1);
+        }
         mem
     }
+
+    /// Reads a byte from memory, taking one M-cycle.
+    fn read<B: Bus>(&mut self, bus: &mut B, addr: u16) -> u8 {
+        self.cycles += M_CYCLE;
+        bus.read(addr)
+    }
+
+    /// Writes a byte to memory, taking one M-cycle.
+    fn write<B: Bus>(&mut self, bus: &mut B, addr: u16, val: u8) {
+        self.cycles += M_CYCLE;
+        bus.write(addr, val);
+    }
+
+    /// Spends one M-cycle without accessing memory.
+    fn idle<B: Bus>(&mut self, bus: &mut B) {
+        self.cycles += M_CYCLE;
+        bus.tick();
+    }
 }
 
 #[derive(Clone, Copy, Default)]
-struct Flags {
-    zero: bool,
-    neg: bool,
-    half_carry: bool,
-    carry: bool,
+pub struct Flags {
+    pub zero: bool,
+    pub neg: bool,
+    pub half_carry: bool,
+    pub carry: bool,
+}
+
+impl From<Flags> for u8 {
+    fn from(value: Flags) -> Self {
+        (value.zero as u8) << 7
+            | (value.neg as u8) << 6
+            | (value.half_carry as u8) << 5
+            | (value.carry as u8) << 4
+    }
 }
 
 impl From<u8> for Flags {
@@ -52,21 +233,21 @@ impl From<u8> for Flags {
     }
 }
 
-#[derive(Default)]
-struct Registers {
-    a: u8,
-    b: u8,
-    c: u8,
-    d: u8,
-    e: u8,
-    h: u8,
-    l: u8,
-
-    flags: Flags,
+#[derive(Clone, Default)]
+pub struct Registers {
+    pub a: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+
+    pub flags: Flags,
     /// Points to the next instruction of the program.
-    prog_counter: u16,
+    pub prog_counter: u16,
     /// Points to the top of the stack.
-    stack_pointer: u16,
+    pub stack_pointer: u16,
 }
 
 #[derive(Clone, Copy)]
@@ -82,12 +263,41 @@ enum Register8 {
 
 #[derive(Clone, Copy)]
 enum Register16 {
+    AF,
     BC,
     DE,
     HL,
     SP,
 }
 
+impl Registers {
+    /// Returns the registers as the boot ROM of `model` leaves them, when it jumps to the cartridge.
+    ///
+    /// See <https://gbdev.io/pandocs/Power_Up_Sequence.html#cpu-registers>
+    pub fn after_boot(model: Model) -> Self {
+        // Flags actually depend on the header checksum, but a correct one is assumed.
+        let [a, f, b, c, d, e, h, l] = match model {
+            Model::Dmg0 => [0x01, 0x00, 0xFF, 0x13, 0x00, 0xC1, 0x84, 0x03],
+            Model::Dmg => [0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Mgb => [0xFF, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+            Model::Sgb => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+            Model::Sgb2 => [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+        };
+        Self {
+            a,
+            b,
+            c,
+            d,
+            e,
+            h,
+            l,
+            flags: f.into(),
+            prog_counter: 0x0100,
+            stack_pointer: 0xFFFE,
+        }
+    }
+}
+
 impl ops::Index<Register8> for Registers {
     type Output = u8;
 
@@ -121,6 +331,7 @@ impl ops::IndexMut<Register8> for Registers {
 impl Registers {
     fn combined(&self, reg: Register16) -> u16 {
         u16::from_be_bytes(match reg {
+            Register16::AF => [self.a, self.flags.into()],
             Register16::BC => [self.b, self.c],
             Register16::DE => [self.d, self.e],
             Register16::HL => [self.h, self.l],
@@ -133,6 +344,12 @@ impl Registers {
     fn set_combined(&mut self, reg: Register16, val: u16) {
         let bytes = val.to_be_bytes();
         let (reg_high, reg_low) = match reg {
+            Register16::AF => {
+                // The lower nibble of F is hardwired to zero.
+                self.a = bytes[0];
+                self.flags = bytes[1].into();
+                return;
+            }
             Register16::BC => (&mut self.b, &mut self.c),
             Register16::DE => (&mut self.d, &mut self.e),
             Register16::HL => (&mut self.h, &mut self.l),
@@ -0,0 +1,302 @@
+//! The `disasm` module decodes SM83 machine code into human-readable assembly.
+//! Addresses in the I/O page are printed with the name of the register they map to.
+//!
+//! See <https://gbdev.io/gb-opcodes/optables/>
+
+use std::{fmt, ops::Range};
+
+use crate::hardware::Bus;
+
+/// A decoded instruction.
+pub struct Instruction {
+    /// Address of the first byte of the instruction.
+    pub addr: u16,
+    /// Raw bytes composing the instruction, opcode included.
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    /// Comma-separated operands. It is empty if the instruction takes no operands.
+    pub operands: String,
+}
+
+impl Instruction {
+    /// Returns the length of the instruction in bytes.
+    pub fn size(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.operands.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{} {}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+/// Decodes the instruction starting at `addr`.
+/// Bytes are fetched through `read`, so any memory can be disassembled.
+pub fn decode(addr: u16, read: impl Fn(u16) -> u8) -> Instruction {
+    let opcode = read(addr);
+    let imm8 = read(addr.wrapping_add(1));
+    let imm16 = u16::from_le_bytes([imm8, read(addr.wrapping_add(2))]);
+
+    let (mnemonic, operands, len) = if opcode == 0xCB {
+        let (mnemonic, operands) = decode_cb(imm8);
+        (mnemonic, operands, 2)
+    } else {
+        decode_base(addr, opcode, imm8, imm16)
+    };
+    Instruction {
+        addr,
+        bytes: (0..len).map(|i| read(addr.wrapping_add(i))).collect(),
+        mnemonic,
+        operands,
+    }
+}
+
+/// Disassembles all instructions starting within `range`.
+/// Memory is peeked, so reading it has no side effects.
+pub fn disassemble(bus: &impl Bus, range: Range<u16>) -> Vec<Instruction> {
+    let mut instrs = Vec::new();
+    let mut addr = range.start;
+    while range.contains(&addr) {
+        let instr = decode(addr, |addr| bus.peek(addr));
+        let (next, overflow) = addr.overflowing_add(instr.size());
+        instrs.push(instr);
+        if overflow {
+            break;
+        }
+        addr = next;
+    }
+    instrs
+}
+
+const REGS8: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const REGS16: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const REGS16_STACK: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const REGS16_ADDR: [&str; 4] = ["(BC)", "(DE)", "(HL+)", "(HL-)"];
+const CONDITIONS: [&str; 4] = ["NZ", "Z", "NC", "C"];
+
+fn decode_base(addr: u16, opcode: u8, imm8: u8, imm16: u16) -> (&'static str, String, u16) {
+    // Opcodes are laid out in an octal-friendly way: xxyyyzzz.
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 0b111) as usize;
+    let z = (opcode & 0b111) as usize;
+    let pair = y >> 1;
+    let relative = addr.wrapping_add(2).wrapping_add_signed(imm8 as i8 as i16);
+
+    let none = String::new;
+    match (x, z) {
+        (0, 0) => match y {
+            0 => ("NOP", none(), 1),
+            1 => ("LD", format!("({}), SP", address(imm16)), 3),
+            2 => ("STOP", none(), 2),
+            3 => ("JR", format!("${relative:04X}"), 2),
+            _ => ("JR", format!("{}, ${relative:04X}", CONDITIONS[y - 4]), 2),
+        },
+        (0, 1) if y.is_multiple_of(2) => ("LD", format!("{}, ${imm16:04X}", REGS16[pair]), 3),
+        (0, 1) => ("ADD", format!("HL, {}", REGS16[pair]), 1),
+        (0, 2) if y.is_multiple_of(2) => ("LD", format!("{}, A", REGS16_ADDR[pair]), 1),
+        (0, 2) => ("LD", format!("A, {}", REGS16_ADDR[pair]), 1),
+        (0, 3) if y.is_multiple_of(2) => ("INC", REGS16[pair].to_string(), 1),
+        (0, 3) => ("DEC", REGS16[pair].to_string(), 1),
+        (0, 4) => ("INC", REGS8[y].to_string(), 1),
+        (0, 5) => ("DEC", REGS8[y].to_string(), 1),
+        (0, 6) => ("LD", format!("{}, ${imm8:02X}", REGS8[y]), 2),
+        (0, 7) => (
+            ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"][y],
+            none(),
+            1,
+        ),
+        (1, _) if y == 6 && z == 6 => ("HALT", none(), 1),
+        (1, _) => ("LD", format!("{}, {}", REGS8[y], REGS8[z]), 1),
+        (2, _) => alu(y, REGS8[z].to_string(), 1),
+        (3, 0) => match y {
+            0..=3 => ("RET", CONDITIONS[y].to_string(), 1),
+            4 => ("LDH", format!("({}), A", address(0xFF00 | imm8 as u16)), 2),
+            5 => ("ADD", format!("SP, {}", imm8 as i8), 2),
+            6 => ("LDH", format!("A, ({})", address(0xFF00 | imm8 as u16)), 2),
+            _ => ("LD", format!("HL, SP{:+}", imm8 as i8), 2),
+        },
+        (3, 1) => match y {
+            0 | 2 | 4 | 6 => ("POP", REGS16_STACK[pair].to_string(), 1),
+            1 => ("RET", none(), 1),
+            3 => ("RETI", none(), 1),
+            5 => ("JP", "HL".to_string(), 1),
+            _ => ("LD", "SP, HL".to_string(), 1),
+        },
+        (3, 2) => match y {
+            0..=3 => ("JP", format!("{}, ${imm16:04X}", CONDITIONS[y]), 3),
+            4 => ("LD", "(C), A".to_string(), 1),
+            5 => ("LD", format!("({}), A", address(imm16)), 3),
+            6 => ("LD", "A, (C)".to_string(), 1),
+            _ => ("LD", format!("A, ({})", address(imm16)), 3),
+        },
+        (3, 3) => match y {
+            0 => ("JP", format!("${imm16:04X}"), 3),
+            6 => ("DI", none(), 1),
+            7 => ("EI", none(), 1),
+            _ => ("ILLEGAL", format!("${opcode:02X}"), 1),
+        },
+        (3, 4) if y < 4 => ("CALL", format!("{}, ${imm16:04X}", CONDITIONS[y]), 3),
+        (3, 5) if y.is_multiple_of(2) => ("PUSH", REGS16_STACK[pair].to_string(), 1),
+        (3, 5) if y == 1 => ("CALL", format!("${imm16:04X}"), 3),
+        (3, 6) => alu(y, format!("${imm8:02X}"), 2),
+        (3, 7) => ("RST", format!("${:02X}", y * 8), 1),
+        _ => ("ILLEGAL", format!("${opcode:02X}"), 1),
+    }
+}
+
+fn alu(op: usize, operand: String, len: u16) -> (&'static str, String, u16) {
+    let (mnemonic, uses_a) = [
+        ("ADD", true),
+        ("ADC", true),
+        ("SUB", false),
+        ("SBC", true),
+        ("AND", false),
+        ("XOR", false),
+        ("OR", false),
+        ("CP", false),
+    ][op];
+    let operands = if uses_a {
+        format!("A, {operand}")
+    } else {
+        operand
+    };
+    (mnemonic, operands, len)
+}
+
+fn decode_cb(prefix: u8) -> (&'static str, String) {
+    let y = ((prefix >> 3) & 0b111) as usize;
+    let reg = REGS8[(prefix & 0b111) as usize];
+    match prefix >> 6 {
+        0 => (
+            ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"][y],
+            reg.to_string(),
+        ),
+        1 => ("BIT", format!("{y}, {reg}")),
+        2 => ("RES", format!("{y}, {reg}")),
+        _ => ("SET", format!("{y}, {reg}")),
+    }
+}
+
+/// Formats a memory address, replacing it with a register name when possible.
+fn address(addr: u16) -> String {
+    match register_name(addr) {
+        Some(name) => name.to_string(),
+        None => format!("${addr:04X}"),
+    }
+}
+
+/// Returns the conventional name of the hardware register mapped at `addr`.
+pub fn register_name(addr: u16) -> Option<&'static str> {
+    Some(match addr {
+        0xFF00 => "rP1",
+        0xFF01 => "rSB",
+        0xFF02 => "rSC",
+        0xFF04 => "rDIV",
+        0xFF05 => "rTIMA",
+        0xFF06 => "rTMA",
+        0xFF07 => "rTAC",
+        0xFF0F => "rIF",
+        0xFF10 => "rNR10",
+        0xFF11 => "rNR11",
+        0xFF12 => "rNR12",
+        0xFF13 => "rNR13",
+        0xFF14 => "rNR14",
+        0xFF16 => "rNR21",
+        0xFF17 => "rNR22",
+        0xFF18 => "rNR23",
+        0xFF19 => "rNR24",
+        0xFF1A => "rNR30",
+        0xFF1B => "rNR31",
+        0xFF1C => "rNR32",
+        0xFF1D => "rNR33",
+        0xFF1E => "rNR34",
+        0xFF20 => "rNR41",
+        0xFF21 => "rNR42",
+        0xFF22 => "rNR43",
+        0xFF23 => "rNR44",
+        0xFF24 => "rNR50",
+        0xFF25 => "rNR51",
+        0xFF26 => "rNR52",
+        0xFF40 => "rLCDC",
+        0xFF41 => "rSTAT",
+        0xFF42 => "rSCY",
+        0xFF43 => "rSCX",
+        0xFF44 => "rLY",
+        0xFF45 => "rLYC",
+        0xFF46 => "rDMA",
+        0xFF47 => "rBGP",
+        0xFF48 => "rOBP0",
+        0xFF49 => "rOBP1",
+        0xFF4A => "rWY",
+        0xFF4B => "rWX",
+        0xFF50 => "rBANK",
+        0xFFFF => "rIE",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_bytes(bytes: &[u8]) -> Instruction {
+        decode(0x0100, |addr| {
+            bytes
+                .get((addr - 0x0100) as usize)
+                .copied()
+                .unwrap_or_default()
+        })
+    }
+
+    #[test]
+    fn decode_base_table() {
+        let tests: Vec<(&[u8], &str, u16)> = vec![
+            (&[0x00], "NOP", 1),
+            (&[0x01, 0x34, 0x12], "LD BC, $1234", 3),
+            (&[0x08, 0x00, 0xC0], "LD ($C000), SP", 3),
+            (&[0x18, 0xFE], "JR $0100", 2),
+            (&[0x20, 0x05], "JR NZ, $0107", 2),
+            (&[0x22], "LD (HL+), A", 1),
+            (&[0x36, 0x42], "LD (HL), $42", 2),
+            (&[0x76], "HALT", 1),
+            (&[0x7E], "LD A, (HL)", 1),
+            (&[0x8E], "ADC A, (HL)", 1),
+            (&[0x90], "SUB B", 1),
+            (&[0xC3, 0x50, 0x01], "JP $0150", 3),
+            (&[0xCD, 0x00, 0x40], "CALL $4000", 3),
+            (&[0xE0, 0x44], "LDH (rLY), A", 2),
+            (&[0xE8, 0xFF], "ADD SP, -1", 2),
+            (&[0xF1], "POP AF", 1),
+            (&[0xF8, 0x02], "LD HL, SP+2", 2),
+            (&[0xFE, 0x90], "CP $90", 2),
+            (&[0xFF], "RST $38", 1),
+            (&[0xD3], "ILLEGAL $D3", 1),
+        ];
+        for (bytes, expected, len) in tests {
+            let instr = decode_bytes(bytes);
+            assert_eq!(instr.to_string(), expected);
+            assert_eq!(instr.size(), len);
+        }
+    }
+
+    #[test]
+    fn decode_cb_table() {
+        let tests: Vec<(&[u8], &str)> = vec![
+            (&[0xCB, 0x00], "RLC B"),
+            (&[0xCB, 0x37], "SWAP A"),
+            (&[0xCB, 0x7C], "BIT 7, H"),
+            (&[0xCB, 0x86], "RES 0, (HL)"),
+            (&[0xCB, 0xFF], "SET 7, A"),
+        ];
+        for (bytes, expected) in tests {
+            let instr = decode_bytes(bytes);
+            assert_eq!(instr.to_string(), expected);
+            assert_eq!(instr.size(), 2);
+        }
+    }
+}
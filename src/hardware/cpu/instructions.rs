@@ -1,71 +1,71 @@
 use crate::hardware::cpu::*;
 
-pub fn execute(cpu: &mut Cpu, hw: &mut Hardware, opcode: u8) -> u8 {
+pub fn execute<B: Bus>(cpu: &mut Cpu, bus: &mut B, opcode: u8) {
     use {Operand::*, Register16::*, Register8::*, Sign::*};
     match opcode {
         0x00 => nop(cpu),
-        0x01 => ld_register16_immediate(cpu, hw, BC),
-        0x02 => ld_addr_from_register8(cpu, hw, BC, A),
-        0x03 => inc_register16(cpu, BC, 1),
+        0x01 => ld_register16_immediate(cpu, bus, BC),
+        0x02 => ld_addr_from_register8(cpu, bus, BC, A),
+        0x03 => inc_register16(cpu, bus, BC, 1),
         0x04 => inc_register8(cpu, B, 1),
         0x05 => inc_register8(cpu, B, -1),
-        0x06 => ld_register8_immediate(cpu, hw, B),
+        0x06 => ld_register8_immediate(cpu, bus, B),
         0x07 => rotate_circular_a(cpu, Direction::Left),
-        0x08 => ld_from_stack_pointer_immediate(cpu, hw),
-        0x09 => add_register16(cpu, HL, BC),
-        0x0A => ld_register8_from_addr(cpu, hw, A, BC),
-        0x0B => inc_register16(cpu, BC, -1),
+        0x08 => ld_from_stack_pointer_immediate(cpu, bus),
+        0x09 => add_register16(cpu, bus, HL, BC),
+        0x0A => ld_register8_from_addr(cpu, bus, A, BC),
+        0x0B => inc_register16(cpu, bus, BC, -1),
         0x0C => inc_register8(cpu, C, 1),
         0x0D => inc_register8(cpu, C, -1),
-        0x0E => ld_register8_immediate(cpu, hw, C),
+        0x0E => ld_register8_immediate(cpu, bus, C),
         0x0F => rotate_circular_a(cpu, Direction::Right),
-        0x10 => stop(cpu, hw),
-        0x11 => ld_register16_immediate(cpu, hw, DE),
-        0x12 => ld_addr_from_register8(cpu, hw, DE, A),
-        0x13 => inc_register16(cpu, DE, 1),
+        0x10 => stop(cpu, bus),
+        0x11 => ld_register16_immediate(cpu, bus, DE),
+        0x12 => ld_addr_from_register8(cpu, bus, DE, A),
+        0x13 => inc_register16(cpu, bus, DE, 1),
         0x14 => inc_register8(cpu, D, 1),
         0x15 => inc_register8(cpu, D, -1),
-        0x16 => ld_register8_immediate(cpu, hw, D),
+        0x16 => ld_register8_immediate(cpu, bus, D),
         0x17 => rotate_a(cpu, Direction::Left),
-        0x18 => jump_relative(cpu, hw, true),
-        0x19 => add_register16(cpu, HL, DE),
-        0x1A => ld_register8_from_addr(cpu, hw, A, DE),
-        0x1B => inc_register16(cpu, DE, -1),
+        0x18 => jump_relative(cpu, bus, true),
+        0x19 => add_register16(cpu, bus, HL, DE),
+        0x1A => ld_register8_from_addr(cpu, bus, A, DE),
+        0x1B => inc_register16(cpu, bus, DE, -1),
         0x1C => inc_register8(cpu, E, 1),
         0x1D => inc_register8(cpu, E, -1),
-        0x1E => ld_register8_immediate(cpu, hw, E),
+        0x1E => ld_register8_immediate(cpu, bus, E),
         0x1F => rotate_a(cpu, Direction::Right),
-        0x20 => jump_relative(cpu, hw, !cpu.regs.flags.zero),
-        0x21 => ld_register16_immediate(cpu, hw, HL),
-        0x22 => ld_addr_from_a_increment(cpu, hw, 1),
-        0x23 => inc_register16(cpu, HL, 1),
+        0x20 => jump_relative(cpu, bus, !cpu.regs.flags.zero),
+        0x21 => ld_register16_immediate(cpu, bus, HL),
+        0x22 => ld_addr_from_a_increment(cpu, bus, 1),
+        0x23 => inc_register16(cpu, bus, HL, 1),
         0x24 => inc_register8(cpu, H, 1),
         0x25 => inc_register8(cpu, H, -1),
-        0x26 => ld_register8_immediate(cpu, hw, H),
+        0x26 => ld_register8_immediate(cpu, bus, H),
         0x27 => daa(cpu),
-        0x28 => jump_relative(cpu, hw, cpu.regs.flags.zero),
-        0x29 => add_register16(cpu, HL, HL),
-        0x2A => ld_a_from_addr_increment(cpu, hw, 1),
-        0x2B => inc_register16(cpu, HL, -1),
+        0x28 => jump_relative(cpu, bus, cpu.regs.flags.zero),
+        0x29 => add_register16(cpu, bus, HL, HL),
+        0x2A => ld_a_from_addr_increment(cpu, bus, 1),
+        0x2B => inc_register16(cpu, bus, HL, -1),
         0x2C => inc_register8(cpu, L, 1),
         0x2D => inc_register8(cpu, L, -1),
-        0x2E => ld_register8_immediate(cpu, hw, L),
+        0x2E => ld_register8_immediate(cpu, bus, L),
         0x2F => cpl(cpu),
-        0x30 => jump_relative(cpu, hw, !cpu.regs.flags.carry),
-        0x31 => ld_register16_immediate(cpu, hw, SP),
-        0x32 => ld_addr_from_a_increment(cpu, hw, -1),
-        0x33 => inc_register16(cpu, SP, 1),
-        0x34 => inc_addr(cpu, hw, 1),
-        0x35 => inc_addr(cpu, hw, -1),
-        0x36 => ld_addr_from_immediate(cpu, hw),
+        0x30 => jump_relative(cpu, bus, !cpu.regs.flags.carry),
+        0x31 => ld_register16_immediate(cpu, bus, SP),
+        0x32 => ld_addr_from_a_increment(cpu, bus, -1),
+        0x33 => inc_register16(cpu, bus, SP, 1),
+        0x34 => inc_addr(cpu, bus, 1),
+        0x35 => inc_addr(cpu, bus, -1),
+        0x36 => ld_addr_from_immediate(cpu, bus),
         0x37 => scf(cpu),
-        0x38 => jump_relative(cpu, hw, cpu.regs.flags.carry),
-        0x39 => add_register16(cpu, HL, SP),
-        0x3A => ld_a_from_addr_increment(cpu, hw, -1),
-        0x3B => inc_register16(cpu, SP, -1),
+        0x38 => jump_relative(cpu, bus, cpu.regs.flags.carry),
+        0x39 => add_register16(cpu, bus, HL, SP),
+        0x3A => ld_a_from_addr_increment(cpu, bus, -1),
+        0x3B => inc_register16(cpu, bus, SP, -1),
         0x3C => inc_register8(cpu, A, 1),
         0x3D => inc_register8(cpu, A, -1),
-        0x3E => ld_register8_immediate(cpu, hw, A),
+        0x3E => ld_register8_immediate(cpu, bus, A),
         0x3F => ccf(cpu),
         0x40 => ld_register8(cpu, B, B),
         0x41 => ld_register8(cpu, B, C),
@@ -73,7 +73,7 @@ pub fn execute(cpu: &mut Cpu, hw: &mut Hardware, opcode: u8) -> u8 {
         0x43 => ld_register8(cpu, B, E),
         0x44 => ld_register8(cpu, B, H),
         0x45 => ld_register8(cpu, B, L),
-        0x46 => ld_register8_from_addr(cpu, hw, B, HL),
+        0x46 => ld_register8_from_addr(cpu, bus, B, HL),
         0x47 => ld_register8(cpu, B, A),
         0x48 => ld_register8(cpu, C, B),
         0x49 => ld_register8(cpu, C, C),
@@ -81,7 +81,7 @@ pub fn execute(cpu: &mut Cpu, hw: &mut Hardware, opcode: u8) -> u8 {
         0x4B => ld_register8(cpu, C, E),
         0x4C => ld_register8(cpu, C, H),
         0x4D => ld_register8(cpu, C, L),
-        0x4E => ld_register8_from_addr(cpu, hw, C, HL),
+        0x4E => ld_register8_from_addr(cpu, bus, C, HL),
         0x4F => ld_register8(cpu, C, A),
         0x50 => ld_register8(cpu, D, B),
         0x51 => ld_register8(cpu, D, C),
@@ -89,7 +89,7 @@ pub fn execute(cpu: &mut Cpu, hw: &mut Hardware, opcode: u8) -> u8 {
         0x53 => ld_register8(cpu, D, E),
         0x54 => ld_register8(cpu, D, H),
         0x55 => ld_register8(cpu, D, L),
-        0x56 => ld_register8_from_addr(cpu, hw, D, HL),
+        0x56 => ld_register8_from_addr(cpu, bus, D, HL),
         0x57 => ld_register8(cpu, D, A),
         0x58 => ld_register8(cpu, E, B),
         0x59 => ld_register8(cpu, E, C),
@@ -97,7 +97,7 @@ pub fn execute(cpu: &mut Cpu, hw: &mut Hardware, opcode: u8) -> u8 {
         0x5B => ld_register8(cpu, E, E),
         0x5C => ld_register8(cpu, E, H),
         0x5D => ld_register8(cpu, E, L),
-        0x5E => ld_register8_from_addr(cpu, hw, E, HL),
+        0x5E => ld_register8_from_addr(cpu, bus, E, HL),
         0x5F => ld_register8(cpu, E, A),
         0x60 => ld_register8(cpu, H, B),
         0x61 => ld_register8(cpu, H, C),
@@ -105,7 +105,7 @@ pub fn execute(cpu: &mut Cpu, hw: &mut Hardware, opcode: u8) -> u8 {
         0x63 => ld_register8(cpu, H, E),
         0x64 => ld_register8(cpu, H, H),
         0x65 => ld_register8(cpu, H, L),
-        0x66 => ld_register8_from_addr(cpu, hw, H, HL),
+        0x66 => ld_register8_from_addr(cpu, bus, H, HL),
         0x67 => ld_register8(cpu, H, A),
         0x68 => ld_register8(cpu, L, B),
         0x69 => ld_register8(cpu, L, C),
@@ -113,179 +113,206 @@ pub fn execute(cpu: &mut Cpu, hw: &mut Hardware, opcode: u8) -> u8 {
         0x6B => ld_register8(cpu, L, E),
         0x6C => ld_register8(cpu, L, H),
         0x6D => ld_register8(cpu, L, L),
-        0x6E => ld_register8_from_addr(cpu, hw, L, HL),
+        0x6E => ld_register8_from_addr(cpu, bus, L, HL),
         0x6F => ld_register8(cpu, L, A),
-        0x70 => ld_addr_from_register8(cpu, hw, HL, B),
-        0x71 => ld_addr_from_register8(cpu, hw, HL, C),
-        0x72 => ld_addr_from_register8(cpu, hw, HL, D),
-        0x73 => ld_addr_from_register8(cpu, hw, HL, E),
-        0x74 => ld_addr_from_register8(cpu, hw, HL, H),
-        0x75 => ld_addr_from_register8(cpu, hw, HL, L),
-        0x76 => halt(cpu),
-        0x77 => ld_addr_from_register8(cpu, hw, HL, A),
+        0x70 => ld_addr_from_register8(cpu, bus, HL, B),
+        0x71 => ld_addr_from_register8(cpu, bus, HL, C),
+        0x72 => ld_addr_from_register8(cpu, bus, HL, D),
+        0x73 => ld_addr_from_register8(cpu, bus, HL, E),
+        0x74 => ld_addr_from_register8(cpu, bus, HL, H),
+        0x75 => ld_addr_from_register8(cpu, bus, HL, L),
+        0x76 => halt(cpu, bus),
+        0x77 => ld_addr_from_register8(cpu, bus, HL, A),
         0x78 => ld_register8(cpu, A, B),
         0x79 => ld_register8(cpu, A, C),
         0x7A => ld_register8(cpu, A, D),
         0x7B => ld_register8(cpu, A, E),
         0x7C => ld_register8(cpu, A, H),
         0x7D => ld_register8(cpu, A, L),
-        0x7E => ld_register8_from_addr(cpu, hw, A, HL),
+        0x7E => ld_register8_from_addr(cpu, bus, A, HL),
         0x7F => ld_register8(cpu, A, A),
-        0x80 => add_register8(cpu, Reg(B), Positive, false),
-        0x81 => add_register8(cpu, Reg(C), Positive, false),
-        0x82 => add_register8(cpu, Reg(D), Positive, false),
-        0x83 => add_register8(cpu, Reg(E), Positive, false),
-        0x84 => add_register8(cpu, Reg(H), Positive, false),
-        0x85 => add_register8(cpu, Reg(L), Positive, false),
-        0x86 => add_register8(cpu, Addr(HL, hw), Positive, false),
-        0x87 => add_register8(cpu, Reg(A), Positive, false),
-        0x88 => add_register8(cpu, Reg(B), Positive, true),
-        0x89 => add_register8(cpu, Reg(C), Positive, true),
-        0x8A => add_register8(cpu, Reg(D), Positive, true),
-        0x8B => add_register8(cpu, Reg(E), Positive, true),
-        0x8C => add_register8(cpu, Reg(H), Positive, true),
-        0x8D => add_register8(cpu, Reg(L), Positive, true),
-        0x8E => add_register8(cpu, Addr(HL, hw), Positive, true),
-        0x8F => add_register8(cpu, Reg(A), Positive, true),
-        0x90 => add_register8(cpu, Reg(B), Negative, false),
-        0x91 => add_register8(cpu, Reg(C), Negative, false),
-        0x92 => add_register8(cpu, Reg(D), Negative, false),
-        0x93 => add_register8(cpu, Reg(E), Negative, false),
-        0x94 => add_register8(cpu, Reg(H), Negative, false),
-        0x95 => add_register8(cpu, Reg(L), Negative, false),
-        0x96 => add_register8(cpu, Addr(HL, hw), Negative, false),
-        0x97 => add_register8(cpu, Reg(A), Negative, false),
-        0x98 => add_register8(cpu, Reg(B), Negative, true),
-        0x99 => add_register8(cpu, Reg(C), Negative, true),
-        0x9A => add_register8(cpu, Reg(D), Negative, true),
-        0x9B => add_register8(cpu, Reg(E), Negative, true),
-        0x9C => add_register8(cpu, Reg(H), Negative, true),
-        0x9D => add_register8(cpu, Reg(L), Negative, true),
-        0x9E => add_register8(cpu, Addr(HL, hw), Negative, true),
-        0x9F => add_register8(cpu, Reg(A), Negative, true),
-        0xA0 => and_register8(cpu, Reg(B)),
-        0xA1 => and_register8(cpu, Reg(C)),
-        0xA2 => and_register8(cpu, Reg(D)),
-        0xA3 => and_register8(cpu, Reg(E)),
-        0xA4 => and_register8(cpu, Reg(H)),
-        0xA5 => and_register8(cpu, Reg(L)),
-        0xA6 => and_register8(cpu, Addr(HL, hw)),
-        0xA7 => and_register8(cpu, Reg(A)),
-        0xA8 => xor_register8(cpu, Reg(B)),
-        0xA9 => xor_register8(cpu, Reg(C)),
-        0xAA => xor_register8(cpu, Reg(D)),
-        0xAB => xor_register8(cpu, Reg(E)),
-        0xAC => xor_register8(cpu, Reg(H)),
-        0xAD => xor_register8(cpu, Reg(L)),
-        0xAE => xor_register8(cpu, Addr(HL, hw)),
-        0xAF => xor_register8(cpu, Reg(A)),
-        0xB0 => or_register8(cpu, Reg(B)),
-        0xB1 => or_register8(cpu, Reg(C)),
-        0xB2 => or_register8(cpu, Reg(D)),
-        0xB3 => or_register8(cpu, Reg(E)),
-        0xB4 => or_register8(cpu, Reg(H)),
-        0xB5 => or_register8(cpu, Reg(L)),
-        0xB6 => or_register8(cpu, Addr(HL, hw)),
-        0xB7 => or_register8(cpu, Reg(A)),
-        0xB8 => cp_register8(cpu, Reg(B)),
-        0xB9 => cp_register8(cpu, Reg(C)),
-        0xBA => cp_register8(cpu, Reg(D)),
-        0xBB => cp_register8(cpu, Reg(E)),
-        0xBC => cp_register8(cpu, Reg(H)),
-        0xBD => cp_register8(cpu, Reg(L)),
-        0xBE => cp_register8(cpu, Addr(HL, hw)),
-        0xBF => cp_register8(cpu, Reg(A)),
-        0xC0 => ret(cpu, hw, Some(!cpu.regs.flags.zero)),
-        0xC1 => pop(cpu, hw, BC),
-        0xC2 => jump_absolute(cpu, hw, !cpu.regs.flags.zero),
-        0xC3 => jump_absolute(cpu, hw, true),
-        0xC4 => call(cpu, hw, !cpu.regs.flags.zero),
-        0xC5 => push(cpu, hw, BC),
-        0xC6 => add_immediate(cpu, hw, Positive, false),
-        0xC7 => rst(cpu, hw, 0x00),
-        0xC8 => ret(cpu, hw, Some(cpu.regs.flags.zero)),
-        0xC9 => ret(cpu, hw, None),
-        0xCA => jump_absolute(cpu, hw, cpu.regs.flags.zero),
+        0x80 => add_register8(cpu, bus, Reg(B), Positive, false),
+        0x81 => add_register8(cpu, bus, Reg(C), Positive, false),
+        0x82 => add_register8(cpu, bus, Reg(D), Positive, false),
+        0x83 => add_register8(cpu, bus, Reg(E), Positive, false),
+        0x84 => add_register8(cpu, bus, Reg(H), Positive, false),
+        0x85 => add_register8(cpu, bus, Reg(L), Positive, false),
+        0x86 => add_register8(cpu, bus, Addr(HL), Positive, false),
+        0x87 => add_register8(cpu, bus, Reg(A), Positive, false),
+        0x88 => add_register8(cpu, bus, Reg(B), Positive, true),
+        0x89 => add_register8(cpu, bus, Reg(C), Positive, true),
+        0x8A => add_register8(cpu, bus, Reg(D), Positive, true),
+        0x8B => add_register8(cpu, bus, Reg(E), Positive, true),
+        0x8C => add_register8(cpu, bus, Reg(H), Positive, true),
+        0x8D => add_register8(cpu, bus, Reg(L), Positive, true),
+        0x8E => add_register8(cpu, bus, Addr(HL), Positive, true),
+        0x8F => add_register8(cpu, bus, Reg(A), Positive, true),
+        0x90 => add_register8(cpu, bus, Reg(B), Negative, false),
+        0x91 => add_register8(cpu, bus, Reg(C), Negative, false),
+        0x92 => add_register8(cpu, bus, Reg(D), Negative, false),
+        0x93 => add_register8(cpu, bus, Reg(E), Negative, false),
+        0x94 => add_register8(cpu, bus, Reg(H), Negative, false),
+        0x95 => add_register8(cpu, bus, Reg(L), Negative, false),
+        0x96 => add_register8(cpu, bus, Addr(HL), Negative, false),
+        0x97 => add_register8(cpu, bus, Reg(A), Negative, false),
+        0x98 => add_register8(cpu, bus, Reg(B), Negative, true),
+        0x99 => add_register8(cpu, bus, Reg(C), Negative, true),
+        0x9A => add_register8(cpu, bus, Reg(D), Negative, true),
+        0x9B => add_register8(cpu, bus, Reg(E), Negative, true),
+        0x9C => add_register8(cpu, bus, Reg(H), Negative, true),
+        0x9D => add_register8(cpu, bus, Reg(L), Negative, true),
+        0x9E => add_register8(cpu, bus, Addr(HL), Negative, true),
+        0x9F => add_register8(cpu, bus, Reg(A), Negative, true),
+        0xA0 => and_register8(cpu, bus, Reg(B)),
+        0xA1 => and_register8(cpu, bus, Reg(C)),
+        0xA2 => and_register8(cpu, bus, Reg(D)),
+        0xA3 => and_register8(cpu, bus, Reg(E)),
+        0xA4 => and_register8(cpu, bus, Reg(H)),
+        0xA5 => and_register8(cpu, bus, Reg(L)),
+        0xA6 => and_register8(cpu, bus, Addr(HL)),
+        0xA7 => and_register8(cpu, bus, Reg(A)),
+        0xA8 => xor_register8(cpu, bus, Reg(B)),
+        0xA9 => xor_register8(cpu, bus, Reg(C)),
+        0xAA => xor_register8(cpu, bus, Reg(D)),
+        0xAB => xor_register8(cpu, bus, Reg(E)),
+        0xAC => xor_register8(cpu, bus, Reg(H)),
+        0xAD => xor_register8(cpu, bus, Reg(L)),
+        0xAE => xor_register8(cpu, bus, Addr(HL)),
+        0xAF => xor_register8(cpu, bus, Reg(A)),
+        0xB0 => or_register8(cpu, bus, Reg(B)),
+        0xB1 => or_register8(cpu, bus, Reg(C)),
+        0xB2 => or_register8(cpu, bus, Reg(D)),
+        0xB3 => or_register8(cpu, bus, Reg(E)),
+        0xB4 => or_register8(cpu, bus, Reg(H)),
+        0xB5 => or_register8(cpu, bus, Reg(L)),
+        0xB6 => or_register8(cpu, bus, Addr(HL)),
+        0xB7 => or_register8(cpu, bus, Reg(A)),
+        0xB8 => cp_register8(cpu, bus, Reg(B)),
+        0xB9 => cp_register8(cpu, bus, Reg(C)),
+        0xBA => cp_register8(cpu, bus, Reg(D)),
+        0xBB => cp_register8(cpu, bus, Reg(E)),
+        0xBC => cp_register8(cpu, bus, Reg(H)),
+        0xBD => cp_register8(cpu, bus, Reg(L)),
+        0xBE => cp_register8(cpu, bus, Addr(HL)),
+        0xBF => cp_register8(cpu, bus, Reg(A)),
+        0xC0 => ret(cpu, bus, Some(!cpu.regs.flags.zero)),
+        0xC1 => pop(cpu, bus, BC),
+        0xC2 => jump_absolute(cpu, bus, !cpu.regs.flags.zero),
+        0xC3 => jump_absolute(cpu, bus, true),
+        0xC4 => call(cpu, bus, !cpu.regs.flags.zero),
+        0xC5 => push(cpu, bus, BC),
+        0xC6 => add_register8(cpu, bus, Immediate, Positive, false),
+        0xC7 => rst(cpu, bus, 0x00),
+        0xC8 => ret(cpu, bus, Some(cpu.regs.flags.zero)),
+        0xC9 => ret(cpu, bus, None),
+        0xCA => jump_absolute(cpu, bus, cpu.regs.flags.zero),
         0xCB => {
-            let prefix = cpu.pop_prog_counter(hw);
-            cbprefix::execute(cpu, hw, prefix)
+            let prefix = cpu.pop_prog_counter(bus);
+            cbprefix::execute(cpu, bus, prefix)
         }
-        0xCC => call(cpu, hw, cpu.regs.flags.zero),
-        0xCD => call(cpu, hw, true),
-        0xCE => add_immediate(cpu, hw, Positive, true),
-        0xCF => rst(cpu, hw, 0x08),
-        0xD0 => ret(cpu, hw, Some(!cpu.regs.flags.carry)),
-        0xD1 => pop(cpu, hw, DE),
-        0xD2 => jump_absolute(cpu, hw, !cpu.regs.flags.carry),
-        0xD3 => unreachable!(),
-        0xD4 => call(cpu, hw, !cpu.regs.flags.carry),
-        0xD5 => push(cpu, hw, DE),
-        0xD6 => add_immediate(cpu, hw, Negative, false),
-        0xD7 => rst(cpu, hw, 0x10),
-        0xD8 => ret(cpu, hw, Some(cpu.regs.flags.carry)),
-        0xD9 => reti(cpu, hw),
-        0xDA => jump_absolute(cpu, hw, cpu.regs.flags.carry),
-        0xDB => unreachable!(),
-        0xDC => call(cpu, hw, cpu.regs.flags.carry),
-        0xDD => unreachable!(),
-        0xDE => add_immediate(cpu, hw, Negative, true),
-        0xDF => rst(cpu, hw, 0x18),
-        _ => unreachable!(),
+        0xCC => call(cpu, bus, cpu.regs.flags.zero),
+        0xCD => call(cpu, bus, true),
+        0xCE => add_register8(cpu, bus, Immediate, Positive, true),
+        0xCF => rst(cpu, bus, 0x08),
+        0xD0 => ret(cpu, bus, Some(!cpu.regs.flags.carry)),
+        0xD1 => pop(cpu, bus, DE),
+        0xD2 => jump_absolute(cpu, bus, !cpu.regs.flags.carry),
+        0xD3 => lock_up(cpu),
+        0xD4 => call(cpu, bus, !cpu.regs.flags.carry),
+        0xD5 => push(cpu, bus, DE),
+        0xD6 => add_register8(cpu, bus, Immediate, Negative, false),
+        0xD7 => rst(cpu, bus, 0x10),
+        0xD8 => ret(cpu, bus, Some(cpu.regs.flags.carry)),
+        0xD9 => reti(cpu, bus),
+        0xDA => jump_absolute(cpu, bus, cpu.regs.flags.carry),
+        0xDB => lock_up(cpu),
+        0xDC => call(cpu, bus, cpu.regs.flags.carry),
+        0xDD => lock_up(cpu),
+        0xDE => add_register8(cpu, bus, Immediate, Negative, true),
+        0xDF => rst(cpu, bus, 0x18),
+        0xE0 => ld_high_addr_immediate_from_a(cpu, bus),
+        0xE1 => pop(cpu, bus, HL),
+        0xE2 => ld_high_addr_c_from_a(cpu, bus),
+        0xE3 => lock_up(cpu),
+        0xE4 => lock_up(cpu),
+        0xE5 => push(cpu, bus, HL),
+        0xE6 => and_register8(cpu, bus, Immediate),
+        0xE7 => rst(cpu, bus, 0x20),
+        0xE8 => add_stack_pointer_immediate(cpu, bus),
+        0xE9 => jump_hl(cpu),
+        0xEA => ld_addr_immediate_from_a(cpu, bus),
+        0xEB => lock_up(cpu),
+        0xEC => lock_up(cpu),
+        0xED => lock_up(cpu),
+        0xEE => xor_register8(cpu, bus, Immediate),
+        0xEF => rst(cpu, bus, 0x28),
+        0xF0 => ld_a_from_high_addr_immediate(cpu, bus),
+        0xF1 => pop(cpu, bus, AF),
+        0xF2 => ld_a_from_high_addr_c(cpu, bus),
+        0xF3 => di(cpu),
+        0xF4 => lock_up(cpu),
+        0xF5 => push(cpu, bus, AF),
+        0xF6 => or_register8(cpu, bus, Immediate),
+        0xF7 => rst(cpu, bus, 0x30),
+        0xF8 => ld_hl_from_stack_pointer_offset(cpu, bus),
+        0xF9 => ld_stack_pointer_from_hl(cpu, bus),
+        0xFA => ld_a_from_addr_immediate(cpu, bus),
+        0xFB => ei(cpu),
+        0xFC => lock_up(cpu),
+        0xFD => lock_up(cpu),
+        0xFE => cp_register8(cpu, bus, Immediate),
+        0xFF => rst(cpu, bus, 0x38),
     }
 }
 
-fn nop(_cpu: &mut Cpu) -> u8 {
-    4
+fn nop(_cpu: &mut Cpu) {}
+
+/// Illegal opcodes hang the CPU until the system is turned off.
+fn lock_up(cpu: &mut Cpu) {
+    cpu.locked_up = Some(cpu.regs.prog_counter.wrapping_sub(1));
 }
 
 #[derive(Clone, Copy)]
-enum Operand<'a> {
+enum Operand {
     Reg(Register8),
-    Addr(Register16, &'a Hardware),
+    Addr(Register16),
+    /// The byte following the opcode.
+    Immediate,
 }
 
-impl<'a> Operand<'a> {
-    fn value(self, cpu: &Cpu) -> u8 {
+impl Operand {
+    fn value<B: Bus>(self, cpu: &mut Cpu, bus: &mut B) -> u8 {
         match self {
             Self::Reg(reg) => cpu.regs[reg],
-            Self::Addr(reg, hw) => hw.read(cpu.regs.combined(reg)),
-        }
-    }
-
-    const fn extra_cycles(self) -> u8 {
-        match self {
-            Self::Reg(_) => 0,
-            Self::Addr(_, _) => 4,
+            Self::Addr(reg) => cpu.read(bus, cpu.regs.combined(reg)),
+            Self::Immediate => cpu.pop_prog_counter(bus),
         }
     }
 }
 
-fn ld_register16_immediate(cpu: &mut Cpu, hw: &mut Hardware, reg: Register16) -> u8 {
-    let lsb = cpu.pop_prog_counter(hw);
-    let msb = cpu.pop_prog_counter(hw);
+fn ld_register16_immediate<B: Bus>(cpu: &mut Cpu, bus: &mut B, reg: Register16) {
+    let lsb = cpu.pop_prog_counter(bus);
+    let msb = cpu.pop_prog_counter(bus);
     cpu.regs.set_combined(reg, u16::from_le_bytes([lsb, msb]));
-    12
 }
 
-fn ld_addr_from_register8(
+fn ld_addr_from_register8<B: Bus>(
     cpu: &mut Cpu,
-    hw: &mut Hardware,
+    bus: &mut B,
     reg_addr: Register16,
     src: Register8,
-) -> u8 {
-    hw.write(cpu.regs.combined(reg_addr), cpu.regs[src]);
-    8
+) {
+    cpu.write(bus, cpu.regs.combined(reg_addr), cpu.regs[src]);
 }
 
-fn inc_register16(cpu: &mut Cpu, reg: Register16, val: i16) -> u8 {
+fn inc_register16<B: Bus>(cpu: &mut Cpu, bus: &mut B, reg: Register16, val: i16) {
     cpu.regs
-        .set_combined(reg, ((cpu.regs.combined(reg) as i16) + val) as u16);
-    8
+        .set_combined(reg, cpu.regs.combined(reg).wrapping_add_signed(val));
+    cpu.idle(bus);
 }
 
-fn ld_register8_immediate(cpu: &mut Cpu, hw: &mut Hardware, reg: Register8) -> u8 {
-    cpu.regs[reg] = cpu.pop_prog_counter(hw);
-    8
+fn ld_register8_immediate<B: Bus>(cpu: &mut Cpu, bus: &mut B, reg: Register8) {
+    cpu.regs[reg] = cpu.pop_prog_counter(bus);
 }
 
 pub enum Direction {
@@ -293,7 +320,7 @@ pub enum Direction {
     Right,
 }
 
-fn rotate_circular_a(cpu: &mut Cpu, dir: Direction) -> u8 {
+fn rotate_circular_a(cpu: &mut Cpu, dir: Direction) {
     match dir {
         Direction::Left => {
             cpu.regs.flags.carry = cpu.regs.a & 0b10000000 != 0;
@@ -307,323 +334,386 @@ fn rotate_circular_a(cpu: &mut Cpu, dir: Direction) -> u8 {
     cpu.regs.flags.zero = false;
     cpu.regs.flags.neg = false;
     cpu.regs.flags.half_carry = false;
-    4
 }
 
-fn ld_from_stack_pointer_immediate(cpu: &mut Cpu, hw: &mut Hardware) -> u8 {
-    let lsb = cpu.pop_prog_counter(hw);
-    let msb = cpu.pop_prog_counter(hw);
+fn ld_from_stack_pointer_immediate<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let lsb = cpu.pop_prog_counter(bus);
+    let msb = cpu.pop_prog_counter(bus);
     let addr = u16::from_le_bytes([lsb, msb]);
-    hw.write(addr, lo(cpu.regs.stack_pointer));
-    hw.write(addr + 1, hi(cpu.regs.stack_pointer));
-    20
+    cpu.write(bus, addr, lo(cpu.regs.stack_pointer));
+    cpu.write(bus, addr.wrapping_add(1), hi(cpu.regs.stack_pointer));
 }
 
-fn add_register16(cpu: &mut Cpu, reg1: Register16, reg2: Register16) -> u8 {
-    let (result, carry) = cpu
-        .regs
-        .combined(reg1)
-        .overflowing_add(cpu.regs.combined(reg2));
+fn add_register16<B: Bus>(cpu: &mut Cpu, bus: &mut B, reg1: Register16, reg2: Register16) {
+    let (val1, val2) = (cpu.regs.combined(reg1), cpu.regs.combined(reg2));
+    let (result, carry) = val1.overflowing_add(val2);
     cpu.regs.set_combined(reg1, result);
-    cpu.regs.flags.zero = false;
-    cpu.regs.flags.half_carry = result >> 8 != 0;
+    // The zero flag is left untouched.
+    cpu.regs.flags.neg = false;
+    cpu.regs.flags.half_carry = (val1 & 0x0FFF) + (val2 & 0x0FFF) > 0x0FFF;
     cpu.regs.flags.carry = carry;
-    8
+    cpu.idle(bus);
 }
 
-fn ld_register8_from_addr(
+fn ld_register8_from_addr<B: Bus>(
     cpu: &mut Cpu,
-    hw: &mut Hardware,
+    bus: &mut B,
     dst: Register8,
     reg_addr: Register16,
-) -> u8 {
-    cpu.regs[dst] = hw.read(cpu.regs.combined(reg_addr));
-    8
+) {
+    cpu.regs[dst] = cpu.read(bus, cpu.regs.combined(reg_addr));
+}
+
+fn inc_register8(cpu: &mut Cpu, reg: Register8, val: i8) {
+    cpu.regs[reg] = inc_byte(cpu, cpu.regs[reg], val);
 }
 
-fn inc_register8(cpu: &mut Cpu, reg: Register8, val: i8) -> u8 {
-    let (result, carry) = cpu.regs[reg].overflowing_add_signed(val);
-    cpu.regs[reg] = result;
+/// Increments or decrements a byte, setting all flags but carry.
+fn inc_byte(cpu: &mut Cpu, byte: u8, val: i8) -> u8 {
+    let result = byte.wrapping_add_signed(val);
     cpu.regs.flags.zero = result == 0;
     cpu.regs.flags.neg = val < 0;
-    cpu.regs.flags.half_carry = carry;
-    4
+    // The half carry is set when the lower nibble overflows (or underflows).
+    cpu.regs.flags.half_carry = if val < 0 {
+        byte & 0x0F == 0x00
+    } else {
+        byte & 0x0F == 0x0F
+    };
+    result
 }
 
-fn stop(cpu: &mut Cpu, hw: &mut Hardware) -> u8 {
-    cpu.halted = false;
-    cpu.pop_prog_counter(hw);
-    4
+fn stop<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    cpu.pop_prog_counter(bus);
+    bus.reset_divider();
+    cpu.stopped = true;
 }
 
-fn rotate_a(cpu: &mut Cpu, dir: Direction) -> u8 {
+fn rotate_a(cpu: &mut Cpu, dir: Direction) {
+    // The carry flag is rotated in, and the bit rotated out becomes the new carry.
+    let old_carry = cpu.regs.flags.carry as u8;
+    let a = cpu.regs.a;
     let (result, carry) = match dir {
-        Direction::Left => cpu.regs.a.overflowing_shl(1),
-        Direction::Right => cpu.regs.a.overflowing_shr(1),
+        Direction::Left => ((a << 1) | old_carry, a & 0b10000000 != 0),
+        Direction::Right => ((a >> 1) | (old_carry << 7), a & 0b00000001 != 0),
     };
     cpu.regs.a = result;
     cpu.regs.flags.zero = false;
     cpu.regs.flags.neg = false;
     cpu.regs.flags.half_carry = false;
     cpu.regs.flags.carry = carry;
-    4
 }
 
-fn jump_relative(cpu: &mut Cpu, hw: &mut Hardware, condition: bool) -> u8 {
+fn jump_relative<B: Bus>(cpu: &mut Cpu, bus: &mut B, condition: bool) {
+    // The offset is read even if the condition is false!
+    let offset = cpu.pop_prog_counter(bus) as i8;
     if !condition {
-        return 8;
+        return;
     }
-    let offset = cpu.pop_prog_counter(hw) as i16;
-    cpu.regs.prog_counter = ((cpu.regs.prog_counter as i16) + offset) as u16;
-    12
+    cpu.regs.prog_counter = cpu.regs.prog_counter.wrapping_add_signed(offset as i16);
+    cpu.idle(bus);
 }
 
-fn ld_addr_from_a_increment(cpu: &mut Cpu, hw: &mut Hardware, inc: i16) -> u8 {
-    ld_addr_from_register8(cpu, hw, Register16::HL, Register8::A);
+fn ld_addr_from_a_increment<B: Bus>(cpu: &mut Cpu, bus: &mut B, inc: i16) {
+    ld_addr_from_register8(cpu, bus, Register16::HL, Register8::A);
     cpu.regs.set_combined(
         Register16::HL,
-        ((cpu.regs.combined(Register16::HL) as i16) + inc) as u16,
+        cpu.regs.combined(Register16::HL).wrapping_add_signed(inc),
     );
-    8
 }
 
-fn daa(cpu: &mut Cpu) -> u8 {
+fn daa(cpu: &mut Cpu) {
+    // Adjusts A to be a valid BCD number, according to the last addition or subtraction.
+    let flags = &mut cpu.regs.flags;
     let a = &mut cpu.regs.a;
-    if (*a & 0b00001111) > 9 || cpu.regs.flags.carry {
-        *a += 0x06;
-    }
-    if (*a >> 4) > 9 || cpu.regs.flags.half_carry {
-        *a += 0x60;
-        cpu.regs.flags.carry = true;
+    if flags.neg {
+        if flags.carry {
+            *a = a.wrapping_sub(0x60);
+        }
+        if flags.half_carry {
+            *a = a.wrapping_sub(0x06);
+        }
     } else {
-        cpu.regs.flags.carry = false;
+        if flags.carry || *a > 0x99 {
+            *a = a.wrapping_add(0x60);
+            flags.carry = true;
+        }
+        if flags.half_carry || (*a & 0x0F) > 0x09 {
+            *a = a.wrapping_add(0x06);
+        }
     }
-    cpu.regs.flags.zero = *a == 0;
-    cpu.regs.flags.half_carry = false;
-    4
+    flags.zero = *a == 0;
+    flags.half_carry = false;
 }
 
-fn ld_a_from_addr_increment(cpu: &mut Cpu, hw: &mut Hardware, inc: i16) -> u8 {
-    ld_register8_from_addr(cpu, hw, Register8::A, Register16::HL);
+fn ld_a_from_addr_increment<B: Bus>(cpu: &mut Cpu, bus: &mut B, inc: i16) {
+    ld_register8_from_addr(cpu, bus, Register8::A, Register16::HL);
     cpu.regs.set_combined(
         Register16::HL,
-        ((cpu.regs.combined(Register16::HL) as i16) + inc) as u16,
+        cpu.regs.combined(Register16::HL).wrapping_add_signed(inc),
     );
-    8
 }
 
-fn cpl(cpu: &mut Cpu) -> u8 {
+fn cpl(cpu: &mut Cpu) {
     cpu.regs.a = !cpu.regs.a;
     cpu.regs.flags.neg = true;
     cpu.regs.flags.half_carry = true;
-    4
 }
 
-fn inc_addr(cpu: &mut Cpu, hw: &mut Hardware, val: i8) -> u8 {
-    let byte = hw.read(cpu.regs.combined(Register16::HL));
-    let (result, carry) = byte.overflowing_add_signed(val);
-    hw.write(cpu.regs.combined(Register16::HL), result);
-    cpu.regs.flags.zero = result == 0;
-    cpu.regs.flags.neg = val < 0;
-    cpu.regs.flags.half_carry = carry;
-    12
+fn inc_addr<B: Bus>(cpu: &mut Cpu, bus: &mut B, val: i8) {
+    let addr = cpu.regs.combined(Register16::HL);
+    let byte = cpu.read(bus, addr);
+    let result = inc_byte(cpu, byte, val);
+    cpu.write(bus, addr, result);
 }
 
-fn ld_addr_from_immediate(cpu: &mut Cpu, hw: &mut Hardware) -> u8 {
-    let byte = cpu.pop_prog_counter(hw);
-    hw.write(cpu.regs.combined(Register16::HL), byte);
-    12
+fn ld_addr_from_immediate<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let byte = cpu.pop_prog_counter(bus);
+    cpu.write(bus, cpu.regs.combined(Register16::HL), byte);
 }
 
-fn scf(cpu: &mut Cpu) -> u8 {
+fn scf(cpu: &mut Cpu) {
     cpu.regs.flags.neg = false;
     cpu.regs.flags.half_carry = false;
     cpu.regs.flags.carry = true;
-    4
 }
 
-fn ccf(cpu: &mut Cpu) -> u8 {
+fn ccf(cpu: &mut Cpu) {
     cpu.regs.flags.neg = false;
     cpu.regs.flags.half_carry = false;
     cpu.regs.flags.carry = !cpu.regs.flags.carry;
-    4
 }
 
-fn ld_register8(cpu: &mut Cpu, dst: Register8, src: Register8) -> u8 {
+fn ld_register8(cpu: &mut Cpu, dst: Register8, src: Register8) {
     cpu.regs[dst] = cpu.regs[src];
-    4
 }
 
-fn halt(cpu: &mut Cpu) -> u8 {
-    cpu.halted = true;
-    4
+fn halt<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    if !cpu.interrupt_enabled && bus.pending_interrupt().is_some() {
+        // The HALT bug: the CPU does not halt, and the next opcode is read twice.
+        cpu.halt_bug = true;
+    } else {
+        cpu.halted = true;
+    }
 }
 
 #[derive(Clone, Copy)]
 enum Sign {
-    Positive = 1,
-    Negative = -1,
+    Positive,
+    Negative,
 }
 
-fn add_register8(cpu: &mut Cpu, operand: Operand, sign: Sign, use_carry: bool) -> u8 {
-    let carry = if use_carry {
-        cpu.regs.flags.carry as i8
-    } else {
-        0
+fn add_register8<B: Bus>(
+    cpu: &mut Cpu,
+    bus: &mut B,
+    operand: Operand,
+    sign: Sign,
+    use_carry: bool,
+) {
+    let value = operand.value(cpu, bus);
+    let carry = (use_carry && cpu.regs.flags.carry) as u8;
+    let a = cpu.regs[Register8::A];
+    let (result, half_carry, carry) = match sign {
+        Sign::Positive => (
+            a.wrapping_add(value).wrapping_add(carry),
+            (a & 0x0F) + (value & 0x0F) + carry > 0x0F,
+            a as u16 + value as u16 + carry as u16 > 0xFF,
+        ),
+        Sign::Negative => (
+            a.wrapping_sub(value).wrapping_sub(carry),
+            (a & 0x0F) < (value & 0x0F) + carry,
+            (a as u16) < value as u16 + carry as u16,
+        ),
     };
-    let (result, carry) = cpu.regs[Register8::A]
-        .overflowing_add_signed((operand.value(cpu) as i8 + carry) * sign as i8);
     cpu.regs[Register8::A] = result;
     cpu.regs.flags.zero = result == 0;
-    cpu.regs.flags.neg = (sign as i8) < 0;
-    cpu.regs.flags.half_carry = result >> 4 != 0;
+    cpu.regs.flags.neg = matches!(sign, Sign::Negative);
+    cpu.regs.flags.half_carry = half_carry;
     cpu.regs.flags.carry = carry;
-    4 + operand.extra_cycles()
 }
 
-fn and_register8(cpu: &mut Cpu, operand: Operand) -> u8 {
-    cpu.regs[Register8::A] &= operand.value(cpu);
+fn and_register8<B: Bus>(cpu: &mut Cpu, bus: &mut B, operand: Operand) {
+    cpu.regs[Register8::A] &= operand.value(cpu, bus);
     cpu.regs.flags.zero = cpu.regs[Register8::A] == 0;
     cpu.regs.flags.neg = false;
     cpu.regs.flags.half_carry = true;
     cpu.regs.flags.carry = false;
-    4 + operand.extra_cycles()
 }
 
-fn xor_register8(cpu: &mut Cpu, operand: Operand) -> u8 {
-    cpu.regs[Register8::A] ^= operand.value(cpu);
+fn xor_register8<B: Bus>(cpu: &mut Cpu, bus: &mut B, operand: Operand) {
+    cpu.regs[Register8::A] ^= operand.value(cpu, bus);
     cpu.regs.flags.zero = cpu.regs[Register8::A] == 0;
     cpu.regs.flags.neg = false;
     cpu.regs.flags.half_carry = false;
     cpu.regs.flags.carry = false;
-    4 + operand.extra_cycles()
 }
 
-fn or_register8(cpu: &mut Cpu, operand: Operand) -> u8 {
-    cpu.regs[Register8::A] |= operand.value(cpu);
+fn or_register8<B: Bus>(cpu: &mut Cpu, bus: &mut B, operand: Operand) {
+    cpu.regs[Register8::A] |= operand.value(cpu, bus);
     cpu.regs.flags.zero = cpu.regs[Register8::A] == 0;
     cpu.regs.flags.neg = false;
     cpu.regs.flags.half_carry = false;
     cpu.regs.flags.carry = false;
-    4 + operand.extra_cycles()
 }
 
-fn cp_register8(cpu: &mut Cpu, operand: Operand) -> u8 {
-    let (result, carry) = cpu.regs[Register8::A].overflowing_sub(operand.value(cpu));
-    cpu.regs.flags.zero = result == 0;
+fn cp_register8<B: Bus>(cpu: &mut Cpu, bus: &mut B, operand: Operand) {
+    let value = operand.value(cpu, bus);
+    let a = cpu.regs[Register8::A];
+    cpu.regs.flags.zero = a == value;
     cpu.regs.flags.neg = true;
-    cpu.regs.flags.half_carry = result >> 4 != 0;
-    cpu.regs.flags.carry = carry;
-    4 + operand.extra_cycles()
+    cpu.regs.flags.half_carry = (a & 0x0F) < (value & 0x0F);
+    cpu.regs.flags.carry = a < value;
 }
 
-fn ret(cpu: &mut Cpu, hw: &mut Hardware, condition: Option<bool>) -> u8 {
-    let (condition, cycles) = match condition {
+fn ret<B: Bus>(cpu: &mut Cpu, bus: &mut B, condition: Option<bool>) {
+    let condition = match condition {
         Some(cond) => {
-            if cond {
-                (true, 20)
-            } else {
-                (false, 8)
-            }
+            // Evaluating the condition takes one extra cycle.
+            cpu.idle(bus);
+            cond
         }
-        None => (true, 16),
+        None => true,
     };
     if !condition {
-        return cycles;
+        return;
     }
-    let lsb = hw.read(cpu.regs.stack_pointer);
-    cpu.regs.stack_pointer += 1;
-    let msb = hw.read(cpu.regs.stack_pointer);
-    cpu.regs.stack_pointer += 1;
-    let addr = u16::from_le_bytes([lsb, msb]);
+    let addr = pop_stack(cpu, bus);
     cpu.regs.prog_counter = addr;
-    cycles
+    cpu.idle(bus);
 }
 
-fn pop(cpu: &mut Cpu, hw: &mut Hardware, dest: Register16) -> u8 {
-    let lsb = hw.read(cpu.regs.stack_pointer);
-    cpu.regs.stack_pointer += 1;
-    let msb = hw.read(cpu.regs.stack_pointer);
-    cpu.regs.stack_pointer += 1;
-    cpu.regs.set_combined(dest, u16::from_le_bytes([lsb, msb]));
-    12
+fn pop<B: Bus>(cpu: &mut Cpu, bus: &mut B, dest: Register16) {
+    let val = pop_stack(cpu, bus);
+    cpu.regs.set_combined(dest, val);
 }
 
-fn jump_absolute(cpu: &mut Cpu, hw: &mut Hardware, condition: bool) -> u8 {
+fn jump_absolute<B: Bus>(cpu: &mut Cpu, bus: &mut B, condition: bool) {
+    // The address is read even if the condition is false!
+    let lsb = cpu.pop_prog_counter(bus);
+    let msb = cpu.pop_prog_counter(bus);
     if !condition {
-        return 12;
+        return;
     }
-    let lsb = cpu.pop_prog_counter(hw);
-    let msb = cpu.pop_prog_counter(hw);
     cpu.regs.prog_counter = u16::from_le_bytes([lsb, msb]);
-    16
+    cpu.idle(bus);
 }
 
-fn call(cpu: &mut Cpu, hw: &mut Hardware, condition: bool) -> u8 {
+fn call<B: Bus>(cpu: &mut Cpu, bus: &mut B, condition: bool) {
     // The subroutine address is read even if the condition is false!
-    let lsb = cpu.pop_prog_counter(hw);
-    let msb = cpu.pop_prog_counter(hw);
+    let lsb = cpu.pop_prog_counter(bus);
+    let msb = cpu.pop_prog_counter(bus);
     if !condition {
-        return 12;
+        return;
     }
-    cpu.regs.stack_pointer -= 1;
-    hw.write(
-        cpu.regs.stack_pointer,
-        cpu.regs.prog_counter.to_be_bytes()[0],
-    );
-    cpu.regs.stack_pointer -= 1;
-    hw.write(
-        cpu.regs.stack_pointer,
-        cpu.regs.prog_counter.to_be_bytes()[1],
-    );
+    cpu.idle(bus);
+    push_stack(cpu, bus, cpu.regs.prog_counter);
     cpu.regs.prog_counter = u16::from_le_bytes([lsb, msb]);
-    24
-}
-
-fn push(cpu: &mut Cpu, hw: &mut Hardware, src: Register16) -> u8 {
-    let bytes = cpu.regs.combined(src).to_be_bytes();
-    cpu.regs.stack_pointer -= 1;
-    hw.write(cpu.regs.stack_pointer, bytes[0]);
-    cpu.regs.stack_pointer -= 1;
-    hw.write(cpu.regs.stack_pointer, bytes[1]);
-    16
 }
 
-fn add_immediate(cpu: &mut Cpu, hw: &mut Hardware, sign: Sign, use_carry: bool) -> u8 {
-    let carry = if use_carry {
-        cpu.regs.flags.carry as i8
-    } else {
-        0
-    };
-    let operand = cpu.pop_prog_counter(hw);
-    let (result, carry) =
-        cpu.regs[Register8::A].overflowing_add_signed((operand as i8 + carry) * sign as i8);
-    cpu.regs[Register8::A] = result;
-    cpu.regs.flags.zero = result == 0;
-    cpu.regs.flags.neg = (sign as i8) < 0;
-    cpu.regs.flags.half_carry = result >> 4 != 0;
-    cpu.regs.flags.carry = carry;
-    4
+fn push<B: Bus>(cpu: &mut Cpu, bus: &mut B, src: Register16) {
+    cpu.idle(bus);
+    push_stack(cpu, bus, cpu.regs.combined(src));
 }
 
-fn rst(cpu: &mut Cpu, hw: &mut Hardware, lsb: u8) -> u8 {
-    cpu.regs.stack_pointer -= 1;
-    hw.write(
-        cpu.regs.stack_pointer,
-        cpu.regs.prog_counter.to_be_bytes()[0],
-    );
-    cpu.regs.stack_pointer -= 1;
-    hw.write(
-        cpu.regs.stack_pointer,
-        cpu.regs.prog_counter.to_be_bytes()[1],
-    );
+fn rst<B: Bus>(cpu: &mut Cpu, bus: &mut B, lsb: u8) {
+    cpu.idle(bus);
+    push_stack(cpu, bus, cpu.regs.prog_counter);
     cpu.regs.prog_counter = u16::from_le_bytes([lsb, 0x00]);
-    16
 }
 
-fn reti(cpu: &mut Cpu, hw: &mut Hardware) -> u8 {
+fn reti<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
     cpu.interrupt_enabled = true;
-    ret(cpu, hw, None)
+    ret(cpu, bus, None)
+}
+
+fn ld_high_addr_immediate_from_a<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let lsb = cpu.pop_prog_counter(bus);
+    cpu.write(bus, u16::from_le_bytes([lsb, 0xFF]), cpu.regs.a);
+}
+
+fn ld_a_from_high_addr_immediate<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let lsb = cpu.pop_prog_counter(bus);
+    cpu.regs.a = cpu.read(bus, u16::from_le_bytes([lsb, 0xFF]));
+}
+
+fn ld_high_addr_c_from_a<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    cpu.write(bus, u16::from_le_bytes([cpu.regs.c, 0xFF]), cpu.regs.a);
+}
+
+fn ld_a_from_high_addr_c<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    cpu.regs.a = cpu.read(bus, u16::from_le_bytes([cpu.regs.c, 0xFF]));
+}
+
+fn ld_addr_immediate_from_a<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let lsb = cpu.pop_prog_counter(bus);
+    let msb = cpu.pop_prog_counter(bus);
+    cpu.write(bus, u16::from_le_bytes([lsb, msb]), cpu.regs.a);
+}
+
+fn ld_a_from_addr_immediate<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let lsb = cpu.pop_prog_counter(bus);
+    let msb = cpu.pop_prog_counter(bus);
+    cpu.regs.a = cpu.read(bus, u16::from_le_bytes([lsb, msb]));
+}
+
+/// Adds a signed offset to the stack pointer, returning the result.
+/// Flags are computed on the lower byte as if it were an unsigned 8-bit addition.
+fn stack_pointer_offset(cpu: &mut Cpu, offset: u8) -> u16 {
+    let sp = cpu.regs.stack_pointer;
+    cpu.regs.flags.zero = false;
+    cpu.regs.flags.neg = false;
+    cpu.regs.flags.half_carry = (lo(sp) & 0x0F) + (offset & 0x0F) > 0x0F;
+    cpu.regs.flags.carry = (lo(sp) as u16) + (offset as u16) > 0xFF;
+    sp.wrapping_add_signed(offset as i8 as i16)
+}
+
+fn add_stack_pointer_immediate<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let offset = cpu.pop_prog_counter(bus);
+    cpu.regs.stack_pointer = stack_pointer_offset(cpu, offset);
+    cpu.idle(bus);
+    cpu.idle(bus);
+}
+
+fn ld_hl_from_stack_pointer_offset<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    let offset = cpu.pop_prog_counter(bus);
+    let result = stack_pointer_offset(cpu, offset);
+    cpu.regs.set_combined(Register16::HL, result);
+    cpu.idle(bus);
+}
+
+fn ld_stack_pointer_from_hl<B: Bus>(cpu: &mut Cpu, bus: &mut B) {
+    cpu.regs.stack_pointer = cpu.regs.combined(Register16::HL);
+    cpu.idle(bus);
+}
+
+fn jump_hl(cpu: &mut Cpu) {
+    cpu.regs.prog_counter = cpu.regs.combined(Register16::HL);
+}
+
+fn di(cpu: &mut Cpu) {
+    cpu.interrupt_enabled = false;
+    cpu.enabling_interrupts = false;
+}
+
+fn ei(cpu: &mut Cpu) {
+    // Interrupts are actually enabled by Cpu::tick, after the next instruction.
+    cpu.enabling_interrupts = true;
+}
+
+/// Pushes a 16-bit value onto the stack, most significant byte first.
+fn push_stack<B: Bus>(cpu: &mut Cpu, bus: &mut B, val: u16) {
+    cpu.regs.stack_pointer = cpu.regs.stack_pointer.wrapping_sub(1);
+    cpu.write(bus, cpu.regs.stack_pointer, hi(val));
+    cpu.regs.stack_pointer = cpu.regs.stack_pointer.wrapping_sub(1);
+    cpu.write(bus, cpu.regs.stack_pointer, lo(val));
+}
+
+/// Pops a 16-bit value from the stack, least significant byte first.
+fn pop_stack<B: Bus>(cpu: &mut Cpu, bus: &mut B) -> u16 {
+    let lsb = cpu.read(bus, cpu.regs.stack_pointer);
+    cpu.regs.stack_pointer = cpu.regs.stack_pointer.wrapping_add(1);
+    let msb = cpu.read(bus, cpu.regs.stack_pointer);
+    cpu.regs.stack_pointer = cpu.regs.stack_pointer.wrapping_add(1);
+    u16::from_le_bytes([lsb, msb])
 }
 
 const fn lo(n: u16) -> u8 {
@@ -0,0 +1,211 @@
+//! Runs the per-opcode JSON test vectors of the SingleStepTests project against the CPU.
+//! Every test sets up the CPU and memory, executes exactly one instruction and compares
+//! registers, memory and the memory access of every M-cycle with the expected ones.
+//!
+//! Test files are not part of this repository. Download them from
+//! <https://github.com/SingleStepTests/sm83> and point the `SM83_TESTS_DIR`
+//! environment variable to the directory containing `00.json`, `01.json` and so on.
+//! When the variable is not set, tests are looked for in `tests/sm83`.
+//! As they need downloading, they only run when asked to, with `cargo test -- --ignored`.
+
+use std::{fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::hardware::{
+    cpu::{Cpu, Flags},
+    Access, Bus, Interrupt, Recorder,
+};
+
+/// An M-cycle as described by tests: address, value and kind of access.
+/// Idle cycles may be `null` or have no address.
+type TestCycle = Option<(Option<u16>, Option<u8>, String)>;
+
+#[derive(Deserialize)]
+struct TestCase {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<TestCycle>,
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct State {
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    f: u8,
+    h: u8,
+    l: u8,
+    pc: u16,
+    sp: u16,
+    #[serde(default)]
+    ime: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+/// Converts an M-cycle as described by tests to the access the CPU is expected to make.
+fn expected_access(cycle: &TestCycle) -> Access {
+    match cycle {
+        Some((Some(addr), Some(val), kind)) if kind.starts_with('r') => Access::Read(*addr, *val),
+        Some((Some(addr), Some(val), kind)) if kind.contains('w') => Access::Write(*addr, *val),
+        _ => Access::Idle,
+    }
+}
+
+/// A flat 64 KiB memory, with no peripherals attached.
+struct TestBus {
+    memory: Vec<u8>,
+}
+
+impl TestBus {
+    fn new() -> Self {
+        Self {
+            memory: vec![0; 0x10000],
+        }
+    }
+}
+
+impl Bus for TestBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        self.memory[addr as usize] = val;
+    }
+
+    fn tick(&mut self) {}
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn pending_interrupt(&self) -> Option<Interrupt> {
+        None
+    }
+
+    fn acknowledge_interrupt(&mut self, _int: Interrupt) {}
+
+    fn joypad_pressed(&self) -> bool {
+        true
+    }
+
+    fn reset_divider(&mut self) {}
+}
+
+fn set_state(cpu: &mut Cpu, bus: &mut TestBus, state: &State) {
+    cpu.regs.a = state.a;
+    cpu.regs.flags = Flags::from(state.f);
+    cpu.regs.b = state.b;
+    cpu.regs.c = state.c;
+    cpu.regs.d = state.d;
+    cpu.regs.e = state.e;
+    cpu.regs.h = state.h;
+    cpu.regs.l = state.l;
+    cpu.regs.prog_counter = state.pc;
+    cpu.regs.stack_pointer = state.sp;
+    cpu.interrupt_enabled = state.ime != 0;
+    for &(addr, val) in &state.ram {
+        bus.memory[addr as usize] = val;
+    }
+}
+
+fn state(cpu: &Cpu, bus: &TestBus, expected: &State) -> State {
+    State {
+        a: cpu.regs.a,
+        b: cpu.regs.b,
+        c: cpu.regs.c,
+        d: cpu.regs.d,
+        e: cpu.regs.e,
+        f: cpu.regs.flags.into(),
+        h: cpu.regs.h,
+        l: cpu.regs.l,
+        pc: cpu.regs.prog_counter,
+        sp: cpu.regs.stack_pointer,
+        // EI takes effect after the next instruction, but tests expect it to be immediate.
+        ime: (cpu.interrupt_enabled || cpu.enabling_interrupts) as u8,
+        ram: expected
+            .ram
+            .iter()
+            .map(|&(addr, _)| (addr, bus.memory[addr as usize]))
+            .collect(),
+    }
+}
+
+/// Runs a single test, returning a description of what went wrong, if anything.
+fn run(test: &TestCase) -> Result<(), String> {
+    let mut cpu = Cpu::new();
+    let mut bus = TestBus::new();
+    set_state(&mut cpu, &mut bus, &test.initial);
+
+    let mut bus = Recorder::new(bus);
+    let ticks = cpu.tick(&mut bus);
+
+    let got = state(&cpu, bus.inner(), &test.expected);
+    if got != test.expected {
+        return Err(format!("expected {:?}, got {got:?}", test.expected));
+    }
+    let expected_cycles: Vec<Access> = test.cycles.iter().map(expected_access).collect();
+    if bus.accesses() != expected_cycles {
+        return Err(format!(
+            "expected cycles {expected_cycles:?}, got {:?}",
+            bus.accesses()
+        ));
+    }
+    if ticks as usize != expected_cycles.len() * 4 {
+        return Err(format!(
+            "expected {} clock ticks, got {ticks}",
+            expected_cycles.len() * 4
+        ));
+    }
+    Ok(())
+}
+
+fn tests_dir() -> PathBuf {
+    match std::env::var_os("SM83_TESTS_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/sm83"),
+    }
+}
+
+#[test]
+#[ignore = "needs tests/sm83"]
+fn sm83_json_tests() {
+    let dir = tests_dir();
+    let entries = fs::read_dir(&dir).unwrap_or_else(|err| {
+        panic!(
+            "cannot read {}: {err}\n\
+             clone https://github.com/SingleStepTests/sm83 and copy its `v1` directory there, \
+             or point SM83_TESTS_DIR to it",
+            dir.display()
+        )
+    });
+    let mut paths: Vec<PathBuf> = entries
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut failures = Vec::new();
+    for path in paths {
+        let tests: Vec<TestCase> =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        // Report only the first failure of each file, as the rest are usually the same bug.
+        if let Some((test, err)) = tests
+            .iter()
+            .find_map(|test| run(test).err().map(|err| (test, err)))
+        {
+            failures.push(format!("{}: {err}", test.name));
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} opcodes failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,175 @@
+//! The `dma` module emulates OAM DMA, which copies 160 bytes to object attribute memory
+//! in the background, one byte per M-cycle, while the CPU keeps running.
+//!
+//! The transfer occupies the bus it reads from, and OAM. Until it ends, the CPU only sees
+//! what is on the other bus, I/O registers and high RAM: that is why games run their DMA
+//! routine from high RAM.
+//!
+//! See <https://gbdev.io/pandocs/OAM_DMA_Transfer.html>
+
+use crate::hardware::M_CYCLE;
+
+/// Bytes copied by a transfer, that is, the size of OAM.
+const LENGTH: u16 = 0xA0;
+
+#[derive(Default)]
+pub struct Dma {
+    /// The last value written to the DMA register: the upper byte of the source address.
+    source: u8,
+    /// A transfer written to the register, which starts after one M-cycle of setup.
+    /// It holds the address of the first byte to copy.
+    starting: Option<u16>,
+    /// Source address of the next byte to copy, if a transfer is running.
+    next: Option<u16>,
+    /// Source address of the byte copied during the current M-cycle, if any.
+    /// The bus it sits on, and OAM, are taken for the whole M-cycle.
+    current: Option<u16>,
+
+    /// Clock ticks elapsed since the last M-cycle boundary.
+    ticks: u8,
+}
+
+/// What the CPU gets when accessing memory that a transfer is using.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Conflict {
+    /// OAM is being written: reads return open bus, writes are ignored.
+    Oam,
+    /// The bus is driven by the transfer: reads return the byte being copied,
+    /// found at the given source address, and writes are ignored.
+    Bus(u16),
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates an idle controller whose register reads as `source`,
+    /// as if a transfer from there already completed.
+    pub fn with_source(source: u8) -> Self {
+        Self {
+            source,
+            ..Default::default()
+        }
+    }
+
+    pub fn read_register(&self) -> u8 {
+        self.source
+    }
+
+    /// Starts a transfer from `val * 0x100`. A running transfer is replaced,
+    /// but it keeps copying during the setup of the new one.
+    pub fn write_register(&mut self, val: u8) {
+        self.source = val;
+        let addr = (val as u16) << 8;
+        // DMA does not see OAM, I/O nor high RAM: the upper addresses reach work RAM instead,
+        // just like the mirror at 0xE000-0xFDFF does for the CPU.
+        let addr = if addr >= 0xE000 { addr - 0x2000 } else { addr };
+        self.starting = Some(addr);
+    }
+
+    /// Advances by a number of clock ticks, and returns how many M-cycles began.
+    /// [`Self::step`] must be called once for each of them.
+    pub fn tick(&mut self, ticks: u8) -> u8 {
+        self.ticks += ticks;
+        let cycles = self.ticks / M_CYCLE;
+        self.ticks %= M_CYCLE;
+        cycles
+    }
+
+    /// Moves to the next M-cycle, and returns the source address of the byte to copy during it.
+    /// The byte goes to the same offset in OAM as in its source page.
+    pub fn step(&mut self) -> Option<u16> {
+        self.current = self.next;
+        self.next = self
+            .current
+            .map(|addr| addr + 1)
+            .filter(|addr| addr & 0xFF < LENGTH);
+        if let Some(addr) = self.starting.take() {
+            self.next = Some(addr);
+        }
+        self.current
+    }
+
+    /// Returns whether the CPU accessing `addr` in the current M-cycle
+    /// conflicts with the running transfer.
+    pub fn conflict(&self, addr: u16) -> Option<Conflict> {
+        let source = self.current?;
+        match addr {
+            0xFE00..=0xFEFF => Some(Conflict::Oam),
+            0xFF00..=0xFFFF => None,
+            _ if is_video_bus(addr) == is_video_bus(source) => Some(Conflict::Bus(source)),
+            _ => None,
+        }
+    }
+}
+
+/// Returns whether an address is reached through the video RAM bus,
+/// rather than the external bus the cartridge and work RAM sit on.
+fn is_video_bus(addr: u16) -> bool {
+    (0x8000..=0x9FFF).contains(&addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a transfer to completion, and returns the source addresses in the order copied.
+    fn run(dma: &mut Dma) -> Vec<u16> {
+        let mut copied = Vec::new();
+        while let Some(addr) = dma.step() {
+            copied.push(addr);
+        }
+        copied
+    }
+
+    #[test]
+    fn transfer_timing() {
+        let mut dma = Dma::new();
+        dma.write_register(0xC1);
+
+        // The M-cycle after the write sets the transfer up: OAM is still accessible.
+        assert_eq!(dma.step(), None);
+        assert_eq!(dma.conflict(0xFE00), None);
+
+        let copied = run(&mut dma);
+        assert_eq!(copied, (0xC100..0xC1A0).collect::<Vec<_>>());
+        assert_eq!(dma.conflict(0xFE00), None);
+        assert_eq!(dma.read_register(), 0xC1);
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut dma = Dma::new();
+        dma.write_register(0x80);
+        dma.step();
+        dma.step();
+
+        assert_eq!(dma.conflict(0xFE10), Some(Conflict::Oam));
+        assert_eq!(dma.conflict(0x9000), Some(Conflict::Bus(0x8000)));
+        assert_eq!(dma.conflict(0xC000), None);
+        assert_eq!(dma.conflict(0xFF44), None);
+        assert_eq!(dma.conflict(0xFF80), None);
+    }
+
+    #[test]
+    fn upper_sources_reach_work_ram() {
+        let mut dma = Dma::new();
+        dma.write_register(0xFE);
+        dma.step();
+        assert_eq!(run(&mut dma).first(), Some(&0xDE00));
+        assert_eq!(dma.read_register(), 0xFE);
+    }
+
+    #[test]
+    fn restart_keeps_copying_during_setup() {
+        let mut dma = Dma::new();
+        dma.write_register(0xC0);
+        dma.step();
+        dma.step();
+        dma.write_register(0xD0);
+
+        assert_eq!(dma.step(), Some(0xC001));
+        assert_eq!(run(&mut dma), (0xD000..0xD0A0).collect::<Vec<_>>());
+    }
+}
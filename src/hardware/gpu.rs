@@ -1,10 +1,22 @@
-use crate::hardware::gpu::lcdc::LcdControl;
+use crate::hardware::{gpu::lcdc::LcdControl, Interruptible};
 
 mod lcdc;
 
+/// Clock ticks the GPU takes to process one line, whether visible or not.
+const TICKS_PER_LINE: u16 = 456;
+/// Lines displayed on the screen. Lines after these are drawn during vertical blank.
+const VISIBLE_LINES: u8 = 144;
+/// Lines in a frame, counting the ones in vertical blank.
+const LINES: u8 = 154;
+/// Clock ticks spent searching objects at the start of a visible line (mode 2).
+const OAM_SCAN_TICKS: u16 = 80;
+/// Clock ticks spent sending pixels to the screen after the object search (mode 3).
+/// It is actually variable, but this is the shortest duration.
+const DRAWING_TICKS: u16 = 172;
+
 pub struct Gpu {
-    /// Contains tiles, that are 8x8 pixel images, with each pixel taking 2 bytes.
-    tile_data: [u8; 4096],
+    /// Contains tiles, that are 8x8 pixel images, with each pixel taking 2 bits.
+    tile_data: [u8; 6144],
     /// First tile map.
     /// A tile map contains the 1-byte indexes of tiles in [`Self::tile_data`].
     /// The actual memory address the index points to depends on the LCDC register.
@@ -13,6 +25,8 @@ pub struct Gpu {
     /// A tile map contains the 1-byte indexes of tiles in [`Self::tile_data`].
     /// The actual memory address the index points to depends on the LCDC register.
     tile_map2: [u8; 1024],
+    /// Object attribute memory (OAM): position, tile and attributes of 40 objects.
+    oam: [u8; 160],
 
     /// The X coordinate of the background viewport.
     /// It corresponds to register SCX.
@@ -29,19 +43,47 @@ pub struct Gpu {
     window_y: u8,
 
     lcd_control: LcdControl,
+    /// Which LCD events raise the STAT interrupt. It corresponds to bits 3-6 of register STAT.
+    stat_sources: u8,
+    /// The line compared to [`Self::line`]. It corresponds to register LYC.
+    line_compare: u8,
+    /// Palette of the background and window. It corresponds to register BGP.
+    background_palette: u8,
+    /// Palettes of objects. They correspond to registers OBP0 and OBP1.
+    object_palettes: [u8; 2],
+
+    /// The line being processed. It corresponds to register LY.
+    line: u8,
+    /// Clock ticks elapsed since the current line started.
+    line_ticks: u16,
+    vblank_interrupt: bool,
+    /// Whether any source enabled in [`Self::stat_sources`] is active. The STAT interrupt
+    /// is raised when it goes from low to high, so sources active together only raise it once.
+    stat_line: bool,
+    stat_interrupt: bool,
 }
 
 impl Default for Gpu {
     fn default() -> Self {
         Self {
-            tile_data: [0; 4096],
+            tile_data: [0; 6144],
             tile_map1: [0; 1024],
             tile_map2: [0; 1024],
+            oam: [0; 160],
             background_x: Default::default(),
             background_y: Default::default(),
             window_x: Default::default(),
             window_y: Default::default(),
             lcd_control: Default::default(),
+            stat_sources: 0,
+            line_compare: 0,
+            background_palette: 0,
+            object_palettes: [0; 2],
+            line: 0,
+            line_ticks: 0,
+            vblank_interrupt: false,
+            stat_line: false,
+            stat_interrupt: false,
         }
     }
 }
@@ -51,19 +93,194 @@ impl Gpu {
         Self::default()
     }
 
+    /// Advances the LCD by a number of clock ticks.
+    /// Nothing is drawn yet: only the current line and mode are kept track of.
+    pub fn tick(&mut self, ticks: u8) {
+        if !self.lcd_control.lcd_enabled() {
+            return;
+        }
+        self.line_ticks += ticks as u16;
+        if self.line_ticks >= TICKS_PER_LINE {
+            self.line_ticks -= TICKS_PER_LINE;
+            self.line = (self.line + 1) % LINES;
+            if self.line == VISIBLE_LINES {
+                self.vblank_interrupt = true;
+            }
+        }
+        self.update_stat_line();
+    }
+
+    /// Returns whether the STAT interrupt was raised since the last call.
+    pub fn take_stat_interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.stat_interrupt)
+    }
+
+    /// Checks the sources of the STAT interrupt, raising it on a rising edge.
+    ///
+    /// See <https://gbdev.io/pandocs/Interrupt_Sources.html#int-48--stat-interrupt>
+    fn update_stat_line(&mut self) {
+        let mode = self.mode();
+        let active = self.lcd_control.lcd_enabled()
+            && (self.stat_sources & 0b01000000 != 0 && self.line == self.line_compare
+                || self.stat_sources & 0b00100000 != 0 && mode == 2
+                || self.stat_sources & 0b00010000 != 0 && mode == 1
+                || self.stat_sources & 0b00001000 != 0 && mode == 0);
+        if active && !self.stat_line {
+            self.stat_interrupt = true;
+        }
+        self.stat_line = active;
+    }
+
+    /// Returns the current mode, as exposed in bits 0-1 of register STAT.
+    fn mode(&self) -> u8 {
+        if !self.lcd_control.lcd_enabled() {
+            return 0;
+        }
+        match (self.line, self.line_ticks) {
+            (VISIBLE_LINES.., _) => 1,
+            (_, ..OAM_SCAN_TICKS) => 2,
+            (_, ticks) if ticks < OAM_SCAN_TICKS + DRAWING_TICKS => 3,
+            _ => 0,
+        }
+    }
+
+    /// Reads video RAM. Addresses are relative to its start, 0x8000.
     pub fn read_vram(&self, addr: u16) -> u8 {
-        todo!()
+        let addr = addr as usize;
+        match addr {
+            0x0000..=0x17FF => self.tile_data[addr],
+            0x1800..=0x1BFF => self.tile_map1[addr - 0x1800],
+            _ => self.tile_map2[addr - 0x1C00],
+        }
     }
 
+    /// Writes video RAM. Addresses are relative to its start, 0x8000.
     pub fn write_vram(&mut self, addr: u16, value: u8) {
-        todo!()
+        let addr = addr as usize;
+        match addr {
+            0x0000..=0x17FF => self.tile_data[addr] = value,
+            0x1800..=0x1BFF => self.tile_map1[addr - 0x1800] = value,
+            _ => self.tile_map2[addr - 0x1C00] = value,
+        }
     }
 
+    /// Reads object attribute memory. Addresses are relative to its start, 0xFE00.
     pub fn read_oam(&self, addr: u16) -> u8 {
-        todo!()
+        self.oam[addr as usize]
     }
 
+    /// Writes object attribute memory. Addresses are relative to its start, 0xFE00.
     pub fn write_oam(&mut self, addr: u16, value: u8) {
-        todo!()
+        self.oam[addr as usize] = value;
+    }
+
+    /// Reads LCD registers. Indexes are relative to 0xFF40, and DMA (index 6) is not included.
+    pub fn read_register(&self, idx: usize) -> u8 {
+        match idx {
+            0x0 => (&self.lcd_control).into(),
+            0x1 => {
+                // Bit 7 is unused and always reads as 1.
+                0b10000000
+                    | self.stat_sources
+                    | ((self.line == self.line_compare) as u8) << 2
+                    | self.mode()
+            }
+            0x2 => self.background_y,
+            0x3 => self.background_x,
+            0x4 => self.line,
+            0x5 => self.line_compare,
+            0x7 => self.background_palette,
+            0x8 => self.object_palettes[0],
+            0x9 => self.object_palettes[1],
+            0xA => self.window_y,
+            0xB => self.window_x,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Writes LCD registers. Indexes are relative to 0xFF40, and DMA (index 6) is not included.
+    pub fn write_register(&mut self, idx: usize, val: u8) {
+        match idx {
+            0x0 => {
+                self.lcd_control = val.into();
+                if !self.lcd_control.lcd_enabled() {
+                    // Turning the LCD off resets it to the start of the frame.
+                    self.line = 0;
+                    self.line_ticks = 0;
+                }
+            }
+            0x1 => self.stat_sources = val & 0b01111000,
+            0x2 => self.background_y = val,
+            0x3 => self.background_x = val,
+            0x4 => (), // LY is read-only.
+            0x5 => self.line_compare = val,
+            0x7 => self.background_palette = val,
+            0x8 => self.object_palettes[0] = val,
+            0x9 => self.object_palettes[1] = val,
+            0xA => self.window_y = val,
+            0xB => self.window_x = val,
+            _ => unreachable!(),
+        }
+        // Writing LCDC, STAT or LYC can activate a source right away.
+        self.update_stat_line();
+    }
+}
+
+impl Interruptible for Gpu {
+    fn take_interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.vblank_interrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks by M-cycles until the STAT interrupt is raised, and returns the line and mode then.
+    fn next_stat_interrupt(gpu: &mut Gpu) -> (u8, u8) {
+        for _ in 0..LINES as u32 * TICKS_PER_LINE as u32 / 4 {
+            gpu.tick(4);
+            if gpu.take_stat_interrupt() {
+                return (gpu.line, gpu.mode());
+            }
+        }
+        panic!("no STAT interrupt in a whole frame");
+    }
+
+    #[test]
+    fn line_compare() {
+        let mut gpu = Gpu::new();
+        gpu.write_register(0x0, 0x80);
+        gpu.write_register(0x5, 2);
+        gpu.write_register(0x1, 0b01000000);
+        assert_eq!(next_stat_interrupt(&mut gpu), (2, 2));
+        // The line stays high for the whole line, so the next one is a frame later.
+        assert_eq!(next_stat_interrupt(&mut gpu).0, 2);
+        assert_eq!(gpu.line_ticks, 0);
+
+        // Sources disabled, nothing is raised.
+        gpu.write_register(0x1, 0);
+        for _ in 0..LINES as u32 * TICKS_PER_LINE as u32 / 4 {
+            gpu.tick(4);
+        }
+        assert!(!gpu.take_stat_interrupt());
+    }
+
+    #[test]
+    fn mode_sources() {
+        let mut gpu = Gpu::new();
+        gpu.write_register(0x0, 0x80);
+        gpu.write_register(0x1, 0b00001000);
+        assert_eq!(next_stat_interrupt(&mut gpu), (0, 0));
+        assert_eq!(next_stat_interrupt(&mut gpu), (1, 0));
+
+        gpu.write_register(0x1, 0b00010000);
+        assert_eq!(next_stat_interrupt(&mut gpu), (VISIBLE_LINES, 1));
+
+        // HBlank and OAM scan follow one another: the line stays high, except during drawing.
+        gpu.write_register(0x1, 0b00101000);
+        assert_eq!(next_stat_interrupt(&mut gpu), (0, 2));
+        assert_eq!(next_stat_interrupt(&mut gpu), (0, 0));
+        assert_eq!(next_stat_interrupt(&mut gpu), (1, 0));
     }
 }
@@ -7,6 +7,12 @@ impl From<u8> for LcdControl {
     }
 }
 
+impl From<&LcdControl> for u8 {
+    fn from(value: &LcdControl) -> Self {
+        value.0.get_range(0..=7)
+    }
+}
+
 impl LcdControl {
     /// Returns whether the LCD is enabled.
     /// If not enabled, the screen is blank.
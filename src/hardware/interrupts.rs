@@ -0,0 +1,87 @@
+//! The `interrupts` module emulates the interrupt controller, which is made of two registers:
+//! IE, which tells which interrupts the CPU is willing to service,
+//! and IF, which tells which interrupts have been requested by peripherals.
+//!
+//! See <https://gbdev.io/pandocs/Interrupts.html>
+
+/// A source of interrupts. Discriminants match the bit
+/// each interrupt occupies in the IE and IF registers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interrupt {
+    VBlank = 0,
+    Stat = 1,
+    Timer = 2,
+    Serial = 3,
+    Joypad = 4,
+}
+
+impl Interrupt {
+    /// All interrupts, sorted by priority. The first one has the highest priority.
+    const ALL: [Self; 5] = [
+        Self::VBlank,
+        Self::Stat,
+        Self::Timer,
+        Self::Serial,
+        Self::Joypad,
+    ];
+
+    /// Returns the address the CPU jumps to when servicing this interrupt.
+    pub const fn vector(self) -> u16 {
+        0x40 + 0x08 * self as u16
+    }
+
+    const fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+#[derive(Default)]
+pub struct Interrupts {
+    /// Interrupts the CPU is allowed to service. It corresponds to register IE.
+    enabled: u8,
+    /// Interrupts requested by peripherals. It corresponds to register IF.
+    requested: u8,
+}
+
+impl Interrupts {
+    /// Bits of IF that are not backed by any interrupt, and always read as 1.
+    const UNUSED_BITS: u8 = 0b11100000;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Flags an interrupt as requested.
+    /// It will be serviced as soon as the CPU allows it.
+    pub fn request(&mut self, int: Interrupt) {
+        self.requested |= int.mask();
+    }
+
+    /// Clears the request of an interrupt, after the CPU started servicing it.
+    pub fn acknowledge(&mut self, int: Interrupt) {
+        self.requested &= !int.mask();
+    }
+
+    /// Returns the highest-priority interrupt that is both requested and enabled.
+    pub fn pending(&self) -> Option<Interrupt> {
+        Interrupt::ALL
+            .into_iter()
+            .find(|int| self.requested & self.enabled & int.mask() != 0)
+    }
+
+    pub fn read_enabled(&self) -> u8 {
+        self.enabled
+    }
+
+    pub fn write_enabled(&mut self, val: u8) {
+        self.enabled = val;
+    }
+
+    pub fn read_requested(&self) -> u8 {
+        self.requested | Self::UNUSED_BITS
+    }
+
+    pub fn write_requested(&mut self, val: u8) {
+        self.requested = val & !Self::UNUSED_BITS;
+    }
+}
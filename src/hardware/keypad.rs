@@ -39,6 +39,13 @@ impl Keypad {
         }
     }
 
+    /// Returns whether any button of a selected row is pressed.
+    pub fn any_pressed(&self) -> bool {
+        [&self.dpad, &self.btns]
+            .iter()
+            .any(|row| row.selected && row.values & 0x0F != 0x0F)
+    }
+
     pub fn read_register(&self, idx: usize) -> u8 {
         if idx > 0 {
             panic!("keypad maps only one byte")
@@ -57,18 +64,27 @@ impl Keypad {
             panic!("keypad maps only one byte")
         }
         self.btns.selected = (val & (1 << 5)) == 0;
-        self.btns.selected = (val & (1 << 4)) == 0;
+        self.dpad.selected = (val & (1 << 4)) == 0;
     }
 }
 
 impl Interruptible for Keypad {
-    fn has_interrupt(&self) -> bool {
-        self.interrupt_raised
+    fn take_interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.interrupt_raised)
     }
 }
 
-#[derive(Default)]
 struct KeyRow {
     selected: bool,
     values: u8,
 }
+
+impl Default for KeyRow {
+    fn default() -> Self {
+        Self {
+            selected: false,
+            // All buttons are released.
+            values: 0x0F,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! The `serial` module emulates the serial port, made of registers SB (data) and SC (control).
+//! No other Game Boy is ever connected: transfers shift in 0xFF, as if the line were idle.
+//!
+//! See <https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html>
+
+use crate::hardware::Interruptible;
+
+/// Clock ticks it takes to shift one bit with the internal clock, which runs at 8192 Hz.
+const TICKS_PER_BIT: u16 = 512;
+
+#[derive(Default)]
+pub struct Serial {
+    /// The byte being transferred. It corresponds to register SB.
+    data: u8,
+    /// Whether a transfer is in progress. It corresponds to bit 7 of register SC.
+    transferring: bool,
+    /// Whether this Game Boy drives the clock. It corresponds to bit 0 of register SC.
+    internal_clock: bool,
+
+    /// Clock ticks elapsed since the transfer started.
+    ticks: u16,
+    interrupt: bool,
+}
+
+impl Serial {
+    /// Bits of SC that are not backed by anything, and always read as 1.
+    const UNUSED_BITS: u8 = 0b01111110;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn tick(&mut self, ticks: u8) {
+        // With an external clock, the transfer waits forever for a peer to drive it.
+        if !self.transferring || !self.internal_clock {
+            return;
+        }
+        self.ticks += ticks as u16;
+        if self.ticks >= 8 * TICKS_PER_BIT {
+            self.data = 0xFF;
+            self.transferring = false;
+            self.interrupt = true;
+        }
+    }
+
+    pub fn read_register(&self, idx: usize) -> u8 {
+        match idx {
+            0 => self.data,
+            1 => Self::UNUSED_BITS | (self.transferring as u8) << 7 | self.internal_clock as u8,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn write_register(&mut self, idx: usize, val: u8) {
+        match idx {
+            0 => self.data = val,
+            1 => {
+                self.transferring = val & 0b10000000 != 0;
+                self.internal_clock = val & 0b00000001 != 0;
+                self.ticks = 0;
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Interruptible for Serial {
+    fn take_interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.interrupt)
+    }
+}
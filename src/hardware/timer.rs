@@ -50,22 +50,38 @@ impl Timer {
         self.enabled = enabled;
     }
 
+    /// Sets the master counter, as the boot ROM leaves it when it ends.
+    pub fn set_divider(&mut self, val: u16) {
+        self.divider = val;
+    }
+
+    pub fn reset_divider(&mut self) {
+        self.divider = 0;
+        self.counter_ticks = 0;
+    }
+
     pub fn read_register(&self, idx: usize) -> u8 {
         match idx {
-            0 => self.divider as u8,
+            0 => (self.divider >> 8) as u8,
             1 => self.counter,
             2 => self.modulo,
-            3 => unimplemented!(), // FIXME: Do games read this value at all?
+            3 => {
+                let frequency = match self.demultiplier {
+                    4 => 1,
+                    16 => 2,
+                    64 => 3,
+                    _ => 0,
+                };
+                // Bits 3-7 are unused and always read as 1.
+                0b11111000 | (self.enabled as u8) << 2 | frequency
+            }
             _ => unreachable!(),
         }
     }
 
     pub fn write_register(&mut self, idx: usize, val: u8) {
         match idx {
-            0 => {
-                self.divider = 0;
-                self.counter_ticks = 0;
-            }
+            0 => self.reset_divider(),
             1 => self.counter = val,
             2 => self.modulo = val,
             3 => {
@@ -84,7 +100,7 @@ impl Timer {
 }
 
 impl Interruptible for Timer {
-    fn has_interrupt(&self) -> bool {
-        self.interrupt
+    fn take_interrupt(&mut self) -> bool {
+        std::mem::take(&mut self.interrupt)
     }
 }
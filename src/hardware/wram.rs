@@ -0,0 +1,71 @@
+//! The `wram` module emulates work RAM, the general-purpose memory inside the console.
+//!
+//! Work RAM is split in two 4 KiB halves: the first is always bank 0, the second shows
+//! one of the remaining banks. The DMG has only bank 1, while the CGB has banks 1-7,
+//! selected through register SVBK.
+//!
+//! See <https://gbdev.io/pandocs/Memory_Map.html>
+
+/// Size, in bytes, of a bank of work RAM, and of each half of its address space.
+const BANK_SIZE: usize = 0x1000;
+
+/// Banks of work RAM on the DMG.
+pub const DMG_BANKS: usize = 2;
+/// Banks of work RAM on the CGB, all but the first selectable through SVBK.
+pub const CGB_BANKS: usize = 8;
+
+pub struct WorkRam {
+    banks: Vec<[u8; BANK_SIZE]>,
+    /// The bank mapped in the second half. It is never 0.
+    bank: usize,
+}
+
+impl WorkRam {
+    /// Creates a work RAM of either [`DMG_BANKS`] or [`CGB_BANKS`] banks.
+    pub fn new(banks: usize) -> Self {
+        assert!(
+            banks == DMG_BANKS || banks == CGB_BANKS,
+            "work RAM has {DMG_BANKS} or {CGB_BANKS} banks, not {banks}"
+        );
+        Self {
+            banks: vec![[0; BANK_SIZE]; banks],
+            bank: 1,
+        }
+    }
+
+    /// Returns whether the second half can show different banks, through SVBK.
+    pub fn switchable(&self) -> bool {
+        self.banks.len() == CGB_BANKS
+    }
+
+    /// Reads from an address relative to the start of work RAM.
+    pub fn read(&self, addr: u16) -> u8 {
+        let (bank, offset) = self.locate(addr);
+        self.banks[bank][offset]
+    }
+
+    /// Writes to an address relative to the start of work RAM.
+    pub fn write(&mut self, addr: u16, val: u8) {
+        let (bank, offset) = self.locate(addr);
+        self.banks[bank][offset] = val;
+    }
+
+    /// Reads register SVBK. Unused bits read as 1.
+    pub fn read_register(&self) -> u8 {
+        0xF8 | self.bank as u8
+    }
+
+    /// Writes register SVBK. Selecting bank 0 selects bank 1 instead.
+    pub fn write_register(&mut self, val: u8) {
+        self.bank = ((val & 0b111) as usize).max(1);
+    }
+
+    fn locate(&self, addr: u16) -> (usize, usize) {
+        let addr = addr as usize;
+        match addr / BANK_SIZE {
+            0 => (0, addr),
+            1 => (self.bank, addr - BANK_SIZE),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,9 +1,15 @@
-use std::{fs::File, io, sync::mpsc};
+use std::{
+    env,
+    fs::{self, File},
+    io,
+    path::PathBuf,
+    sync::mpsc,
+};
 
 use cpal::traits::{DeviceTrait, HostTrait};
 use playful_youngster::{
-    emulator::{Emulator, SAMPLE_RATE},
-    hardware::{keypad::Button, Cartridge},
+    emulator::{debugger::Debugger, gdb, Emulator, SAMPLE_RATE},
+    hardware::{keypad::Button, BootRom, BootRomError, Cartridge, FileStorage, Model},
 };
 use winit::{
     application::ApplicationHandler,
@@ -16,15 +22,118 @@ use winit::{
 
 const AUDIO_BUFFER_SIZE: usize = 1024;
 
-fn main() -> Result<(), Error> {
-    let cartridge = Box::new(File::open("/tmp/cart")?);
+const USAGE: &str =
+    "[--boot-rom PATH] [--model dmg0|dmg|mgb|sgb|sgb2] [--skip-boot] [--debug | --gdb PORT] ROM";
 
-    let evtloop = EventLoop::new()?;
-    evtloop.run_app(&mut Application::new()?)?;
+fn main() -> Result<(), Error> {
+    let options = Options::parse(env::args().skip(1))?;
+    match options.frontend {
+        Frontend::Gui => {
+            let evtloop = EventLoop::new()?;
+            evtloop.run_app(&mut Application::new(&options)?)?;
+        }
+        Frontend::Debugger => {
+            let mut emulator = headless_emulator(&options)?;
+            Debugger::new().run(&mut emulator, io::stdin().lock(), io::stdout())?;
+            emulator.save()?;
+        }
+        Frontend::Gdb(port) => {
+            let mut emulator = headless_emulator(&options)?;
+            eprintln!("waiting for a GDB client on port {port}");
+            gdb::serve(&mut emulator, ("127.0.0.1", port))?;
+            emulator.save()?;
+        }
+    }
 
     Ok(())
 }
 
+/// How the user interacts with the emulator.
+enum Frontend {
+    Gui,
+    /// The interactive command-line debugger.
+    Debugger,
+    /// A GDB stub listening on a local port.
+    Gdb(u16),
+}
+
+struct Options {
+    frontend: Frontend,
+    /// Cartridge ROM to run. Its save file, if it has a battery, is kept beside it.
+    rom: PathBuf,
+    /// Boot ROM to run instead of the embedded one.
+    boot_rom: Option<PathBuf>,
+    /// Model to emulate. If a boot ROM is given, it is trusted to belong to this model,
+    /// otherwise the model is guessed from the boot ROM hash.
+    model: Option<Model>,
+    /// Whether to start from the state the boot ROM leaves, instead of running it.
+    skip_boot: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, Error> {
+        let mut rom = None;
+        let mut options = Self {
+            frontend: Frontend::Gui,
+            rom: PathBuf::new(),
+            boot_rom: None,
+            model: None,
+            skip_boot: false,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--boot-rom" => {
+                    options.boot_rom = Some(args.next().ok_or(Error::Usage(USAGE))?.into());
+                }
+                "--model" => {
+                    let model = args.next().ok_or(Error::Usage(USAGE))?;
+                    options.model = Some(model.parse().map_err(|_| Error::Usage(USAGE))?);
+                }
+                "--skip-boot" => options.skip_boot = true,
+                "--debug" => options.frontend = Frontend::Debugger,
+                "--gdb" => {
+                    let port = args
+                        .next()
+                        .and_then(|port| port.parse().ok())
+                        .ok_or(Error::Usage(USAGE))?;
+                    options.frontend = Frontend::Gdb(port);
+                }
+                path if !path.starts_with("--") && rom.is_none() => rom = Some(path.into()),
+                _ => return Err(Error::Usage(USAGE)),
+            }
+        }
+        options.rom = rom.ok_or(Error::Usage(USAGE))?;
+        Ok(options)
+    }
+
+    fn create_emulator(&self, audio_sender: mpsc::SyncSender<(u8, u8)>) -> Result<Emulator, Error> {
+        let mut emulator = Emulator::new(audio_sender);
+        if let Some(model) = self.model {
+            emulator.set_model(model);
+        }
+        if let Some(path) = &self.boot_rom {
+            let boot_rom = match self.model {
+                Some(model) => BootRom::with_model(fs::read(path)?, model)?,
+                None => BootRom::load(path)?,
+            };
+            emulator.insert_boot_rom(boot_rom);
+        }
+        if self.skip_boot {
+            emulator.skip_boot();
+        }
+        let mut cartridge = Cartridge::new_from_header(File::open(&self.rom)?)?;
+        cartridge.set_storage(Box::new(FileStorage::beside_rom(&self.rom)))?;
+        emulator.insert_cartridge(cartridge);
+        Ok(emulator)
+    }
+}
+
+/// Creates an emulator for debugging sessions, where audio is not played and samples are dropped.
+fn headless_emulator(options: &Options) -> Result<Emulator, Error> {
+    let (audio_sender, _) = mpsc::sync_channel(AUDIO_BUFFER_SIZE);
+    options.create_emulator(audio_sender)
+}
+
 struct Application {
     emulator: Emulator,
 
@@ -33,11 +142,11 @@ struct Application {
 }
 
 impl Application {
-    fn new() -> Result<Self, Error> {
+    fn new(options: &Options) -> Result<Self, Error> {
         let (audio_sender, audio_receiver) = mpsc::sync_channel::<(u8, u8)>(AUDIO_BUFFER_SIZE);
         let audio = Self::init_audio(audio_receiver)?;
         Ok(Self {
-            emulator: Emulator::new(audio_sender),
+            emulator: options.create_emulator(audio_sender)?,
 
             window: None,
             audio,
@@ -110,14 +219,21 @@ impl ApplicationHandler for Application {
                 };
                 self.emulator.set_pressed(button, event.state.is_pressed());
             }
-            WindowEvent::CloseRequested => evtloop.exit(),
+            WindowEvent::CloseRequested => {
+                if let Err(err) = self.emulator.save() {
+                    eprintln!("failed to save: {err}");
+                }
+                evtloop.exit();
+            }
             _ => (),
         }
     }
 
     fn about_to_wait(&mut self, evtloop: &ActiveEventLoop) {
         evtloop.set_control_flow(winit::event_loop::ControlFlow::Poll);
-        self.emulator.process_frame();
+        if let Err(err) = self.emulator.process_frame() {
+            eprintln!("failed to save: {err}");
+        }
     }
 }
 
@@ -131,6 +247,12 @@ pub enum Error {
 
     #[error("failed to initialize audio system: {0}")]
     Audio(String),
+
+    #[error(transparent)]
+    BootRom(#[from] BootRomError),
+
+    #[error("usage: playful-youngster {0}")]
+    Usage(&'static str),
 }
 
 impl From<OsError> for Error {